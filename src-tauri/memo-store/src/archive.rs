@@ -0,0 +1,496 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Component, Path, PathBuf};
+use zip::result::ZipError;
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::encryption::KEY_FILENAME;
+use crate::error::MemoError;
+use crate::ids::{by_filename, MemoIds, FINGERPRINTS_FILENAME, IDS_FILENAME};
+use crate::search;
+use crate::store::{resolve_unique_filename, MemoStore, PinData};
+use crate::trash::{read_trash_entries, write_trash_entries, TrashEntry};
+use crate::utils::atomic::atomic_write;
+use crate::utils::paths::{ensure_dir_exists, is_memo_file};
+
+/// Identifies vault archives in their manifest
+const ARCHIVE_FORMAT: &str = "rakumemo-vault-archive";
+const ARCHIVE_VERSION: u32 = 1;
+
+/// Name of the manifest inside the zip; the memo directory is stored under `vault/`
+const MANIFEST_NAME: &str = "manifest.json";
+const VAULT_PREFIX: &str = "vault/";
+
+/// The manifest stored next to the memo directory in a vault archive
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ArchiveManifest {
+    pub format: String,
+    pub version: u32,
+    pub created_at: String,
+    pub memo_count: usize,
+    pub files: Vec<ArchivedFile>,
+}
+
+/// A file in a vault archive, relative to the memo directory
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ArchivedFile {
+    pub path: String,
+    pub size: u64,
+}
+
+/// What to do when a file from the archive exists with other content already
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ConflictPolicy {
+    /// Keep the existing file
+    Skip,
+    /// Replace the existing file with the archived one
+    Overwrite,
+    /// Restore the archived file next to it with a `_N` suffix
+    KeepBoth,
+}
+
+impl Default for ConflictPolicy {
+    fn default() -> ConflictPolicy {
+        ConflictPolicy::KeepBoth
+    }
+}
+
+/// A file restored under another name because its own was taken
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RenamedFile {
+    pub from: String,
+    pub to: String,
+}
+
+/// What `import_archive` did
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ArchiveRestore {
+    /// Memos and other files written, under their final names
+    pub restored: Vec<String>,
+    pub renamed: Vec<RenamedFile>,
+    /// Files left out because a different one exists and the policy is `Skip`
+    pub skipped: Vec<String>,
+    /// Files that exist with the same content already
+    pub unchanged: usize,
+    pub warnings: Vec<String>,
+}
+
+fn zip_error(context: &str, path: &Path, error: ZipError) -> MemoError {
+    match error {
+        ZipError::Io(e) => MemoError::io(context, path, e),
+        e => MemoError::format(context, Some(path), e),
+    }
+}
+
+/// Whether a file of the memo directory belongs in an archive. The search
+/// index can be rebuilt from the memos; `.bak` copies, temp files, a git
+/// repository and sync state of this machine don't belong in a backup.
+fn is_archived(relative: &str) -> bool {
+    let name = relative.rsplit('/').next().unwrap_or(relative);
+    !(relative == ".search-index.json"
+        || relative == ".sync-state.json"
+        || relative == FINGERPRINTS_FILENAME
+        || relative == ".git"
+        || relative.starts_with(".git/")
+        || name.ends_with(".bak")
+        || (name.starts_with('.') && name.ends_with(".tmp")))
+}
+
+/// Check a path from an archive before joining it onto the memo directory:
+/// relative, `/`-separated and without `.` or `..` parts
+fn is_safe_archive_path(path: &str) -> bool {
+    !path.contains(['\\', ':', '\0'])
+        && path.split('/').all(|part| !part.is_empty() && part != "." && part != "..")
+}
+
+/// Every file below `dir`, hidden ones included, relative to it with `/` separators
+pub(crate) fn walk_all_files(dir: &Path) -> io::Result<Vec<(String, PathBuf)>> {
+    let mut files = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+
+    while let Some(current) = pending.pop() {
+        for entry in fs::read_dir(&current)? {
+            let entry = entry?;
+            let path = entry.path();
+            if entry.file_type()?.is_dir() {
+                pending.push(path);
+                continue;
+            }
+
+            let relative = path.strip_prefix(dir).ok().and_then(|relative| {
+                relative
+                    .components()
+                    .map(|component| match component {
+                        Component::Normal(part) => part.to_str(),
+                        _ => None,
+                    })
+                    .collect::<Option<Vec<_>>>()
+            });
+            if let Some(parts) = relative {
+                files.push((parts.join("/"), path));
+            }
+        }
+    }
+
+    files.sort();
+    Ok(files)
+}
+
+/// Write a restored file, creating its folder first
+fn write_restored(path: &Path, contents: &[u8]) -> Result<(), MemoError> {
+    if let Some(parent) = path.parent() {
+        ensure_dir_exists(&parent.to_path_buf())?;
+    }
+    atomic_write(path, contents).map_err(|e| MemoError::io("Failed to restore file", path, e))
+}
+
+/// Merge a `.pins.json` or `.order.json` map from an archive into the current
+/// one for the files that were restored, by the name each was written under.
+/// Existing entries win unless the policy is `Overwrite`.
+fn merge_map<T>(
+    current: &mut HashMap<String, T>,
+    archived: HashMap<String, T>,
+    written: &HashMap<String, String>,
+    policy: ConflictPolicy,
+) {
+    for (filename, value) in archived {
+        let filename = match written.get(&filename) {
+            Some(filename) => filename.clone(),
+            None => continue,
+        };
+        if policy == ConflictPolicy::Overwrite || !current.contains_key(&filename) {
+            current.insert(filename, value);
+        }
+    }
+}
+
+impl MemoStore {
+    /// Pack the memo directory, with pins, order, trash and history, into a
+    /// zip at `target` and return the manifest stored in it
+    pub fn export_archive(&self, target: &Path) -> Result<ArchiveManifest, MemoError> {
+        let root = self.root();
+        let files = walk_all_files(root).map_err(|e| MemoError::io("Failed to read memo directory", root, e))?;
+
+        // The archive may be written into the memo directory itself
+        let target_dir = target.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or_else(|| Path::new("."));
+        let target_name = target
+            .file_name()
+            .and_then(|name| name.to_str())
+            .ok_or_else(|| MemoError::invalid_input(format!("Invalid archive path: '{}'", target.display())))?;
+        let own_archive = target_dir.canonicalize().ok().map(|dir| dir.join(target_name));
+        let temp_path = target_dir.join(format!(".{}.tmp", target_name));
+
+        let temp_file = File::create(&temp_path).map_err(|e| MemoError::io("Failed to create archive", &temp_path, e))?;
+        let mut zip = ZipWriter::new(temp_file);
+        let options = FileOptions::default().compression_method(CompressionMethod::Deflated);
+
+        let mut manifest = ArchiveManifest {
+            format: ARCHIVE_FORMAT.to_string(),
+            version: ARCHIVE_VERSION,
+            created_at: chrono::Utc::now().to_rfc3339(),
+            memo_count: 0,
+            files: Vec::new(),
+        };
+
+        let result = (|| {
+            for (relative, path) in files {
+                if !is_archived(&relative) || path.canonicalize().ok() == own_archive {
+                    continue;
+                }
+
+                zip.start_file(format!("{}{}", VAULT_PREFIX, relative), options)
+                    .map_err(|e| zip_error("Failed to write archive", target, e))?;
+                let mut file = File::open(&path).map_err(|e| MemoError::io("Failed to read file", &path, e))?;
+                let size = io::copy(&mut file, &mut zip).map_err(|e| MemoError::io("Failed to write archive", target, e))?;
+
+                if !relative.split('/').any(|part| part.starts_with('.')) && is_memo_file(Path::new(&relative)) {
+                    manifest.memo_count += 1;
+                }
+                manifest.files.push(ArchivedFile { path: relative, size });
+            }
+
+            let content = serde_json::to_vec_pretty(&manifest)
+                .map_err(|e| MemoError::format("Failed to serialize archive manifest", None, e))?;
+            zip.start_file(MANIFEST_NAME, options)
+                .map_err(|e| zip_error("Failed to write archive", target, e))?;
+            io::Write::write_all(&mut zip, &content).map_err(|e| MemoError::io("Failed to write archive", target, e))?;
+
+            let file = zip.finish().map_err(|e| zip_error("Failed to write archive", target, e))?;
+            file.sync_all().map_err(|e| MemoError::io("Failed to write archive", target, e))?;
+            fs::rename(&temp_path, target).map_err(|e| MemoError::io("Failed to write archive", target, e))
+        })();
+
+        if result.is_err() {
+            let _ = fs::remove_file(&temp_path);
+        }
+        result.map(|_| manifest)
+    }
+
+    /// Restore a vault archive into this memo directory, which may be empty or
+    /// hold memos already. Memos and attachments that exist with other content
+    /// are handled by `policy`; pins, order, trash and history are merged.
+    pub fn import_archive(&self, source: &Path, policy: ConflictPolicy) -> Result<ArchiveRestore, MemoError> {
+        let root = self.root();
+        let file = File::open(source).map_err(|e| MemoError::io("Failed to open archive", source, e))?;
+        let mut archive = ZipArchive::new(file).map_err(|e| zip_error("Failed to read archive", source, e))?;
+
+        let manifest: ArchiveManifest = {
+            let mut entry = archive
+                .by_name(MANIFEST_NAME)
+                .map_err(|_| MemoError::format("Failed to read archive", Some(source), "not a vault archive"))?;
+            let mut content = String::new();
+            entry
+                .read_to_string(&mut content)
+                .map_err(|e| MemoError::io("Failed to read archive", source, e))?;
+            serde_json::from_str(&content).map_err(|e| MemoError::format("Failed to parse archive manifest", Some(source), e))?
+        };
+        if manifest.format != ARCHIVE_FORMAT || manifest.version > ARCHIVE_VERSION {
+            return Err(MemoError::format(
+                "Failed to read archive",
+                Some(source),
+                format!("unsupported format {} version {}", manifest.format, manifest.version),
+            ));
+        }
+
+        let mut restore = ArchiveRestore::default();
+        let mut entries = Vec::new();
+        for index in 0..archive.len() {
+            let mut entry = archive.by_index(index).map_err(|e| zip_error("Failed to read archive", source, e))?;
+            let relative = match entry.name().strip_prefix(VAULT_PREFIX) {
+                Some(relative) if !entry.is_dir() => relative.to_string(),
+                _ => continue,
+            };
+            if !is_safe_archive_path(&relative) {
+                restore.warnings.push(format!("Ignored unsafe path '{}'", relative));
+                continue;
+            }
+
+            let mut contents = Vec::new();
+            entry
+                .read_to_end(&mut contents)
+                .map_err(|e| MemoError::io("Failed to read archive", source, e))?;
+            entries.push((relative, contents));
+        }
+
+        // Memos and attachments first, so the metadata can follow their renames
+        let (files, metadata): (Vec<_>, Vec<_>) = entries.into_iter().partition(|(relative, _)| !relative.starts_with('.'));
+        let mut renames = HashMap::new();
+        let mut written = HashMap::new();
+
+        // Pins and order in the archive are keyed by the IDs its memos had
+        // there
+        let archived_ids: MemoIds = metadata
+            .iter()
+            .find(|(relative, _)| relative == IDS_FILENAME)
+            .and_then(|(_, contents)| serde_json::from_slice(contents).ok())
+            .unwrap_or_default();
+
+        for (relative, contents) in files {
+            let path = root.join(&relative);
+            let existing = fs::read(&path).ok();
+            if existing.as_deref() == Some(contents.as_slice()) {
+                restore.unchanged += 1;
+                continue;
+            }
+
+            let filename = match (existing, policy) {
+                (None, _) | (Some(_), ConflictPolicy::Overwrite) => relative.clone(),
+                (Some(_), ConflictPolicy::KeepBoth) => {
+                    let name = Path::new(&relative);
+                    match (name.file_stem().and_then(|s| s.to_str()), name.extension().and_then(|s| s.to_str())) {
+                        (Some(stem), Some(ext)) => {
+                            let folder = relative.rsplit_once('/').map_or("", |(folder, _)| folder);
+                            let unique = resolve_unique_filename(&root.join(folder), stem, ext)?;
+                            if folder.is_empty() { unique } else { format!("{}/{}", folder, unique) }
+                        }
+                        _ => {
+                            restore.skipped.push(relative);
+                            continue;
+                        }
+                    }
+                }
+                (Some(_), ConflictPolicy::Skip) => {
+                    restore.skipped.push(relative);
+                    continue;
+                }
+            };
+
+            let path = root.join(&filename);
+            self.mark_write(&path);
+            write_restored(&path, &contents)?;
+            if is_memo_file(&path) {
+                if let Ok(content) = std::str::from_utf8(&contents) {
+                    search::index_memo(root, &filename, content);
+                }
+            }
+            written.insert(relative.clone(), filename.clone());
+            if filename != relative {
+                renames.insert(relative.clone(), filename.clone());
+                restore.renamed.push(RenamedFile { from: relative, to: filename.clone() });
+            }
+            restore.restored.push(filename);
+        }
+
+        // Restored memos keep the IDs they had in the archive where they are
+        // free here, so links by ID and trash entries keep pointing at them
+        let restored_ids: Vec<(String, String)> = archived_ids
+            .iter()
+            .filter_map(|(id, filename)| written.get(filename).map(|written| (id.clone(), written.clone())))
+            .collect();
+        self.restore_memo_ids(&restored_ids)?;
+
+        // History and trash files are only added, never replaced: versions of
+        // a memo that has its own history already would not fit in with it
+        let mut existing_history: HashSet<PathBuf> = HashSet::new();
+        let mut fresh_history: HashSet<PathBuf> = HashSet::new();
+
+        for (relative, contents) in metadata {
+            match relative.as_str() {
+                ".pins.json" => {
+                    let archived: HashMap<String, PinData> = serde_json::from_slice(&contents)
+                        .map_err(|e| MemoError::format("Failed to parse archived pins", Some(source), e))?;
+                    let mut pins = self.read_pin_data().unwrap_or_default();
+                    merge_map(&mut pins, by_filename(archived, &archived_ids), &written, policy);
+                    self.write_pin_data(&pins)?;
+                }
+                ".order.json" => {
+                    let archived: HashMap<String, usize> = serde_json::from_slice(&contents)
+                        .map_err(|e| MemoError::format("Failed to parse archived order", Some(source), e))?;
+                    let mut order = self.read_order_data().unwrap_or_default();
+                    merge_map(&mut order, by_filename(archived, &archived_ids), &written, policy);
+                    self.write_order_data(&order)?;
+                }
+                ".trash/.trash.json" => {
+                    let archived: Vec<TrashEntry> = serde_json::from_slice(&contents)
+                        .map_err(|e| MemoError::format("Failed to parse archived trash", Some(source), e))?;
+                    let mut trash = read_trash_entries(root)?;
+                    let known: HashSet<String> = trash.iter().map(|entry| entry.id.clone()).collect();
+                    trash.extend(archived.into_iter().filter(|entry| !known.contains(&entry.id)));
+                    ensure_dir_exists(&root.join(".trash"))?;
+                    write_trash_entries(root, &trash)?;
+                }
+                KEY_FILENAME => {
+                    let path = root.join(&relative);
+                    match fs::read(&path) {
+                        Err(_) => write_restored(&path, &contents)?,
+                        Ok(existing) if existing != contents => restore.warnings.push(
+                            "This vault has its own passphrase; encrypted memos from the archive need the archive's passphrase to open"
+                                .to_string(),
+                        ),
+                        Ok(_) => {}
+                    }
+                }
+                _ if relative.starts_with(".trash/") => {
+                    let path = root.join(&relative);
+                    if !path.exists() {
+                        write_restored(&path, &contents)?;
+                    }
+                }
+                _ if relative.starts_with(".history/") => {
+                    let mut relative = relative;
+                    for (from, to) in &renames {
+                        if let Some(rest) = relative.strip_prefix(&format!(".history/{}/", from)) {
+                            relative = format!(".history/{}/{}", to, rest);
+                            break;
+                        }
+                    }
+
+                    let path = root.join(&relative);
+                    let dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+                    if !fresh_history.contains(&dir) && !existing_history.contains(&dir) {
+                        if dir.exists() {
+                            existing_history.insert(dir.clone());
+                        } else {
+                            fresh_history.insert(dir.clone());
+                        }
+                    }
+                    if fresh_history.contains(&dir) {
+                        write_restored(&path, &contents)?;
+                    }
+                }
+                // Settings of other tools and anything this version doesn't know
+                _ => {}
+            }
+        }
+
+        // The writes were marked as the app's own, so the watcher stays quiet
+        // about them; give the other restored memos their IDs here instead
+        self.reconcile_memo_ids()?;
+
+        Ok(restore)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::TempDir;
+    use crate::utils::paths::MemoPath;
+
+    #[test]
+    fn test_archive_round_trip_and_conflicts() {
+        let dir = TempDir::new("archive");
+        let source = dir.store("source");
+        let plan = source.import("Plan.md", "v1").unwrap();
+        let plan = MemoPath::parse(&plan.filename).unwrap();
+        source.save("Plan", "v2", Some(&plan), None, false, None).unwrap();
+        source.toggle_pin(&plan).unwrap();
+        let id = source.read(&plan, false).unwrap().id;
+
+        let archive = dir.join("backup.zip");
+        let manifest = source.export_archive(&archive).unwrap();
+        assert_eq!(manifest.memo_count, 1);
+        assert!(manifest.files.iter().any(|file| file.path == ".pins.json"));
+        assert!(manifest.files.iter().any(|file| file.path.starts_with(".history/Plan.md/")));
+
+        // Into an empty directory everything comes back as it was
+        let target = dir.store("target");
+        let restore = target.import_archive(&archive, ConflictPolicy::KeepBoth).unwrap();
+        assert_eq!(restore.restored, vec!["Plan.md".to_string()]);
+        assert_eq!(target.find_memo_by_id(&id).unwrap().as_deref(), Some("Plan.md"));
+        assert_eq!(search::search(target.root(), "v2", 10).unwrap().len(), 1);
+        assert!(target.list().unwrap()[0].pinned);
+        assert_eq!(target.list_versions(&plan).unwrap().len(), 1);
+
+        // A memo changed since keeps its content, the archived one goes next to it
+        target.save("Plan", "v3", Some(&plan), None, false, None).unwrap();
+        let restore = target.import_archive(&archive, ConflictPolicy::KeepBoth).unwrap();
+        assert_eq!(restore.renamed[0].to, "Plan_1.md");
+        assert_eq!(target.read(&plan, false).unwrap().content, "v3");
+        assert_eq!(target.read(&MemoPath::parse("Plan_1.md").unwrap(), false).unwrap().content, "v2");
+
+        // The copy keeps its own ID
+        assert_ne!(target.read(&MemoPath::parse("Plan_1.md").unwrap(), false).unwrap().id, id);
+
+        let restore = target.import_archive(&archive, ConflictPolicy::Skip).unwrap();
+        assert_eq!(restore.skipped, vec!["Plan.md".to_string()]);
+        assert!(restore.restored.is_empty());
+
+        // A skipped file doesn't take the archived pin either
+        let other = dir.store("other");
+        other.import("Plan.md", "unrelated").unwrap();
+        other.import_archive(&archive, ConflictPolicy::Skip).unwrap();
+        assert!(!other.read(&plan, false).unwrap().pinned);
+    }
+
+    #[test]
+    fn test_archive_paths_stay_inside_the_vault() {
+        assert!(is_safe_archive_path("work/Plan.md"));
+        assert!(is_safe_archive_path(".history/Plan.md/versions.json"));
+        assert!(!is_safe_archive_path("../outside.md"));
+        assert!(!is_safe_archive_path("/etc/passwd"));
+        assert!(!is_safe_archive_path("work//Plan.md"));
+        assert!(!is_safe_archive_path("C:/Windows/win.ini"));
+        assert!(!is_archived(".search-index.json"));
+        assert!(!is_archived(".pins.json.bak"));
+        assert!(is_archived(".pins.json"));
+    }
+}
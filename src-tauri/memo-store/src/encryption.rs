@@ -0,0 +1,476 @@
+use argon2::{Algorithm, Argon2, Params, Version};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use zeroize::Zeroizing;
+
+use crate::error::MemoError;
+use crate::history::remove_history;
+use crate::ids::random_id;
+use crate::merge::forget_versions;
+use crate::search;
+use crate::store::{compose_memo_file, file_version, resolve_unique_filename, MemoStore, SavedMemo};
+use crate::trash::read_trash_entries;
+use crate::utils::atomic::atomic_write;
+use crate::utils::front_matter::{parse_front_matter, FrontMatter};
+use crate::utils::paths::{
+    is_encrypted_memo, join_memo_path, memo_folder, relative_memo_path, sanitize_filename, walk_memo_files, MemoPath,
+};
+
+/// Salt, KDF parameters and a check value for the vault passphrase
+pub(crate) const KEY_FILENAME: &str = ".vault-key.json";
+
+/// Marks encrypted memo files; the rest of the file is not human-readable
+const FILE_FORMAT: &str = "rakumemo-encrypted";
+
+/// Encrypted with the vault key to tell a wrong passphrase from a right one
+const CHECK_PLAINTEXT: &[u8] = b"rakumemo vault key";
+
+/// Argon2id cost, the OWASP recommendation for interactive logins
+const KDF_MEMORY_KIB: u32 = 19 * 1024;
+const KDF_ITERATIONS: u32 = 2;
+const KDF_PARALLELISM: u32 = 1;
+
+type VaultKey = Zeroizing<[u8; 32]>;
+
+/// Keys of unlocked vaults by memo directory. They only ever live in memory
+/// and are wiped when the vault is locked or the app exits.
+static KEYS: Lazy<Mutex<HashMap<PathBuf, VaultKey>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Nonce and ciphertext of one encrypted value, base64-encoded
+#[derive(Debug, Serialize, Deserialize)]
+struct Sealed {
+    nonce: String,
+    ciphertext: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct KeyFile {
+    version: u32,
+    memory_kib: u32,
+    iterations: u32,
+    parallelism: u32,
+    salt: String,
+    check: Sealed,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct EncryptedFile {
+    format: String,
+    version: u32,
+    #[serde(flatten)]
+    sealed: Sealed,
+}
+
+/// What an encrypted memo file holds once decrypted. The title lives in here
+/// because the file is named with a random id.
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct SecretMemo {
+    pub(crate) title: String,
+    /// Extension the memo had before it was encrypted, "md" or "txt"
+    pub(crate) ext: String,
+    /// The whole memo, including any front matter
+    pub(crate) content: String,
+}
+
+impl SecretMemo {
+    /// Separate the front matter from the body, like for a plain memo of the same type
+    pub(crate) fn split(&self) -> (Option<FrontMatter>, &str) {
+        if self.ext == "md" {
+            parse_front_matter(&self.content)
+        } else {
+            (None, &self.content)
+        }
+    }
+}
+
+/// Decrypt `content`, read from the encrypted memo file at `path`
+fn open_secret(key: &VaultKey, path: &Path, content: &str) -> Result<SecretMemo, MemoError> {
+    let file: EncryptedFile = serde_json::from_str(content)
+        .ok()
+        .filter(|file: &EncryptedFile| file.format == FILE_FORMAT)
+        .ok_or_else(|| MemoError::format("Failed to read encrypted memo", Some(path), "unknown format"))?;
+
+    let plaintext = open(key, &file.sealed)
+        .ok_or_else(|| MemoError::format("Failed to decrypt memo", Some(path), "wrong key or damaged file"))?;
+    serde_json::from_slice(&plaintext).map_err(|e| MemoError::format("Failed to read encrypted memo", Some(path), e))
+}
+
+fn derive_key(passphrase: &str, key_file: &KeyFile) -> Result<VaultKey, MemoError> {
+    let salt = BASE64
+        .decode(&key_file.salt)
+        .map_err(|e| MemoError::format("Failed to read vault key", None, e))?;
+    let params = Params::new(key_file.memory_kib, key_file.iterations, key_file.parallelism, Some(32))
+        .map_err(|e| MemoError::format("Invalid vault key parameters", None, e))?;
+
+    let mut key = Zeroizing::new([0u8; 32]);
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(passphrase.as_bytes(), &salt, key.as_mut())
+        .map_err(|e| MemoError::format("Failed to derive vault key", None, e))?;
+    Ok(key)
+}
+
+fn seal(key: &VaultKey, plaintext: &[u8]) -> Result<Sealed, MemoError> {
+    let cipher = XChaCha20Poly1305::new(key.as_ref().into());
+    let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(&nonce, plaintext)
+        .map_err(|_| MemoError::Other { message: "Failed to encrypt memo".to_string() })?;
+
+    Ok(Sealed {
+        nonce: BASE64.encode(nonce),
+        ciphertext: BASE64.encode(ciphertext),
+    })
+}
+
+/// Decrypt a sealed value; None means the key is wrong or the data was tampered with
+fn open(key: &VaultKey, sealed: &Sealed) -> Option<Zeroizing<Vec<u8>>> {
+    let nonce = BASE64.decode(&sealed.nonce).ok()?;
+    if nonce.len() != 24 {
+        return None;
+    }
+    let ciphertext = BASE64.decode(&sealed.ciphertext).ok()?;
+
+    XChaCha20Poly1305::new(key.as_ref().into())
+        .decrypt(XNonce::from_slice(&nonce), ciphertext.as_slice())
+        .ok()
+        .map(Zeroizing::new)
+}
+
+impl MemoStore {
+    fn key_file(&self) -> PathBuf {
+        self.root().join(KEY_FILENAME)
+    }
+
+    fn vault_key(&self) -> Option<VaultKey> {
+        let keys = KEYS.lock().unwrap_or_else(|e| e.into_inner());
+        keys.get(self.root()).cloned()
+    }
+
+    /// Whether any memo, in the trash or not, is encrypted with the key
+    fn has_encrypted_memos(&self) -> Result<bool, MemoError> {
+        let root = self.root();
+        let paths = walk_memo_files(root).map_err(|e| MemoError::io("Failed to read memo directory", root, e))?;
+        let encrypted = paths
+            .iter()
+            .filter_map(|path| relative_memo_path(root, path))
+            .any(|filename| is_encrypted_memo(&filename));
+        Ok(encrypted || read_trash_entries(root)?.iter().any(|entry| is_encrypted_memo(&entry.filename)))
+    }
+
+    /// Unlock the encrypted memos of this memo directory with `passphrase`.
+    /// The first unlock sets the passphrase; later ones must match it. A new
+    /// key is only made while there are no encrypted memos: with the key file
+    /// gone, they could never be opened with it.
+    pub fn unlock(&self, passphrase: &str) -> Result<(), MemoError> {
+        if passphrase.is_empty() {
+            return Err(MemoError::invalid_input("Passphrase is empty".to_string()));
+        }
+
+        let key_path = self.key_file();
+        let key = if key_path.exists() {
+            let content = fs::read_to_string(&key_path)
+                .map_err(|e| MemoError::io("Failed to read vault key", &key_path, e))?;
+            let key_file: KeyFile = serde_json::from_str(&content)
+                .map_err(|e| MemoError::format("Failed to parse vault key", Some(&key_path), e))?;
+
+            let key = derive_key(passphrase, &key_file)?;
+            if open(&key, &key_file.check).as_deref().map(Vec::as_slice) != Some(CHECK_PLAINTEXT) {
+                return Err(MemoError::invalid_input("Wrong passphrase".to_string()));
+            }
+            key
+        } else if self.has_encrypted_memos()? {
+            return Err(MemoError::not_found(
+                "The vault key is missing; restore it to open the encrypted memos".to_string(),
+                &key_path,
+            ));
+        } else {
+            let mut salt = [0u8; 16];
+            OsRng.fill_bytes(&mut salt);
+            let mut key_file = KeyFile {
+                version: 1,
+                memory_kib: KDF_MEMORY_KIB,
+                iterations: KDF_ITERATIONS,
+                parallelism: KDF_PARALLELISM,
+                salt: BASE64.encode(salt),
+                check: Sealed { nonce: String::new(), ciphertext: String::new() },
+            };
+
+            let key = derive_key(passphrase, &key_file)?;
+            key_file.check = seal(&key, CHECK_PLAINTEXT)?;
+            let content = serde_json::to_string_pretty(&key_file)
+                .map_err(|e| MemoError::format("Failed to serialize vault key", None, e))?;
+            atomic_write(&key_path, content)
+                .map_err(|e| MemoError::io("Failed to write vault key", &key_path, e))?;
+            key
+        };
+
+        let mut keys = KEYS.lock().unwrap_or_else(|e| e.into_inner());
+        keys.insert(self.root().to_path_buf(), key);
+        Ok(())
+    }
+
+    /// Forget the key; encrypted memos show up locked again
+    pub fn lock(&self) {
+        let mut keys = KEYS.lock().unwrap_or_else(|e| e.into_inner());
+        keys.remove(self.root());
+    }
+
+    pub fn is_unlocked(&self) -> bool {
+        self.vault_key().is_some()
+    }
+
+    /// Decrypt an encrypted memo file, or return None while the vault is locked
+    pub(crate) fn read_secret(&self, path: &Path) -> Result<Option<SecretMemo>, MemoError> {
+        let key = match self.vault_key() {
+            Some(key) => key,
+            None => return Ok(None),
+        };
+
+        let content = fs::read_to_string(path)
+            .map_err(|e| MemoError::io("Failed to read file", path, e))?;
+        open_secret(&key, path, &content).map(Some)
+    }
+
+    /// Whether a memo is Markdown, going by the extension it had before it
+    /// was encrypted for `.enc` files. Locked memos count as Markdown.
+    pub(crate) fn is_markdown_memo(&self, filename: &str) -> bool {
+        if !is_encrypted_memo(filename) {
+            return filename.ends_with(".md");
+        }
+        match self.read_secret(&self.root().join(filename)) {
+            Ok(Some(secret)) => secret.ext == "md",
+            _ => true,
+        }
+    }
+
+    /// Encrypt `memo` into the file at `path`
+    pub(crate) fn write_secret(&self, path: &Path, memo: &SecretMemo) -> Result<(), MemoError> {
+        let key = self
+            .vault_key()
+            .ok_or_else(|| MemoError::locked("Unlock the vault to edit encrypted memos".to_string(), path))?;
+
+        let plaintext = Zeroizing::new(
+            serde_json::to_vec(memo).map_err(|e| MemoError::format("Failed to serialize memo", None, e))?,
+        );
+        let file = EncryptedFile {
+            format: FILE_FORMAT.to_string(),
+            version: 1,
+            sealed: seal(&key, &plaintext)?,
+        };
+        let content = serde_json::to_string(&file)
+            .map_err(|e| MemoError::format("Failed to serialize memo", None, e))?;
+
+        self.mark_write(path);
+        atomic_write(path, content).map_err(|e| MemoError::io("Failed to write memo", path, e))
+    }
+
+    /// `save` for encrypted memos: the file keeps its random name and no history
+    /// or search index entry is written, since both would hold plain text
+    pub(crate) fn save_secret(
+        &self,
+        filename: &str,
+        title: &str,
+        content: &str,
+        front_matter: Option<FrontMatter>,
+        expected_version: Option<&str>,
+    ) -> Result<SavedMemo, MemoError> {
+        let path = self.root().join(filename);
+        let key = self
+            .vault_key()
+            .ok_or_else(|| MemoError::locked(format!("Memo '{}' is locked", filename), &path))?;
+        let file = fs::read_to_string(&path).ok();
+        if let Some(expected_version) = expected_version {
+            self.ensure_version(filename, expected_version, file.as_deref().map(str::as_bytes))?;
+        }
+        let file = file.ok_or_else(|| MemoError::not_found(format!("Memo '{}' not found", filename), &path))?;
+        let previous = open_secret(&key, &path, &file)?;
+
+        let content = compose_memo_file(&previous.ext, content, front_matter, Some(&previous.content))?;
+        self.write_secret(
+            &path,
+            &SecretMemo {
+                title: sanitize_filename(title),
+                ext: previous.ext,
+                content,
+            },
+        )?;
+
+        Ok(SavedMemo {
+            filename: filename.to_string(),
+            version: file_version(&path)?,
+        })
+    }
+
+    /// Encrypt a plain memo in place and return its new, random filename. Its
+    /// history and the versions kept for merging are dropped since they hold
+    /// plain copies; pin and order move along. Copies outside the memo file
+    /// stay as they were: git commits, the sync target and trashed memos
+    /// still hold the old plain text.
+    pub fn encrypt_memo(&self, filename: &MemoPath) -> Result<String, MemoError> {
+        if is_encrypted_memo(filename) {
+            return Ok(filename.to_string());
+        }
+        let memo = self.read(filename, true)?;
+
+        let folder = memo_folder(filename);
+        // A random name, so the file name doesn't give the title away
+        let new_filename = join_memo_path(folder, &format!("{}.enc", random_id()));
+        let new_path = self.root().join(&new_filename);
+        let ext = Path::new(filename.as_str())
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or("md")
+            .to_string();
+
+        self.write_secret(
+            &new_path,
+            &SecretMemo {
+                title: memo.title,
+                ext,
+                content: memo.content,
+            },
+        )?;
+
+        let old_path = self.root().join(filename);
+        self.mark_write(&old_path);
+        fs::remove_file(&old_path).map_err(|e| MemoError::io("Failed to delete old file", &old_path, e))?;
+        forget_versions(&old_path);
+        search::remove_memo(self.root(), filename);
+        let _ = remove_history(self.root(), filename);
+        self.carry_pin_and_order(filename, &new_filename, true);
+
+        Ok(new_filename)
+    }
+
+    /// Turn an encrypted memo back into a plain file named after its title
+    pub fn decrypt_memo(&self, filename: &MemoPath) -> Result<String, MemoError> {
+        let old_path = self.root().join(filename);
+        let secret = self
+            .read_secret(&old_path)?
+            .ok_or_else(|| MemoError::locked("Unlock the vault to decrypt memos".to_string(), &old_path))?;
+
+        let folder = memo_folder(filename);
+        let ext = if secret.ext == "txt" { "txt" } else { "md" };
+        let name = resolve_unique_filename(&self.root().join(folder), &sanitize_filename(&secret.title), ext)?;
+        let new_filename = join_memo_path(folder, &name);
+        let new_path = self.root().join(&new_filename);
+
+        self.mark_write(&new_path);
+        atomic_write(&new_path, &secret.content)
+            .map_err(|e| MemoError::io("Failed to write memo", &new_path, e))?;
+        search::index_memo(self.root(), &new_filename, &secret.content);
+
+        self.mark_write(&old_path);
+        fs::remove_file(&old_path).map_err(|e| MemoError::io("Failed to delete old file", &old_path, e))?;
+        self.carry_pin_and_order(filename, &new_filename, true);
+
+        Ok(new_filename)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::TempDir;
+
+    #[test]
+    fn test_encrypt_lock_and_unlock() {
+        let dir = TempDir::new("encryption");
+        let store = dir.store("memos");
+        let memo = store.import("Diary.md", "dear diary").unwrap();
+
+        store.unlock("correct horse").unwrap();
+        let filename = store.encrypt_memo(&MemoPath::parse(&memo.filename).unwrap()).unwrap();
+        let path = MemoPath::parse(&filename).unwrap();
+        assert!(is_encrypted_memo(&filename));
+        assert!(!fs::read_to_string(store.root().join(&filename)).unwrap().contains("dear diary"));
+        assert_eq!(store.list().unwrap()[0].title, "Diary");
+
+        store.lock();
+        let memos = store.list().unwrap();
+        assert!(memos[0].locked);
+        assert_eq!(memos[0].title, "");
+        assert!(matches!(store.read(&path, false), Err(MemoError::Locked { .. })));
+        assert!(store.unlock("wrong horse").is_err());
+
+        store.unlock("correct horse").unwrap();
+        assert_eq!(store.save("Diary", "dear diary, again", Some(&path), None, false, None).unwrap().filename, filename);
+        assert_eq!(store.read(&path, false).unwrap().content, "dear diary, again");
+
+        let decrypted = store.decrypt_memo(&path).unwrap();
+        assert_eq!(decrypted, "Diary.md");
+        assert_eq!(fs::read_to_string(store.root().join(&decrypted)).unwrap(), "dear diary, again");
+
+        store.lock();
+    }
+
+    #[test]
+    fn test_unlock_without_key_file_keeps_encrypted_memos() {
+        let dir = TempDir::new("encryption-key");
+        let store = dir.store("memos");
+        let memo = store.import("Diary.md", "dear diary").unwrap();
+
+        store.unlock("correct horse").unwrap();
+        store.encrypt_memo(&MemoPath::parse(&memo.filename).unwrap()).unwrap();
+        store.lock();
+
+        let key_file = store.root().join(KEY_FILENAME);
+        let key = fs::read(&key_file).unwrap();
+        fs::remove_file(&key_file).unwrap();
+        assert!(matches!(store.unlock("correct horse"), Err(MemoError::NotFound { .. })));
+        assert!(!key_file.exists());
+
+        fs::write(&key_file, key).unwrap();
+        store.unlock("correct horse").unwrap();
+        store.lock();
+    }
+
+    #[test]
+    fn test_encrypt_drops_memo_from_saved_search_index() {
+        let dir = TempDir::new("encryption-index");
+        let store = dir.store("memos");
+        let memo = store.import("Secret.md", "zanzibar").unwrap();
+        let index_file = store.root().join(".search-index.json");
+
+        assert_eq!(search::search(store.root(), "zanzibar", 10).unwrap().len(), 1);
+        assert!(fs::read_to_string(&index_file).unwrap().contains("zanzibar"));
+
+        store.unlock("correct horse").unwrap();
+        store.encrypt_memo(&MemoPath::parse(&memo.filename).unwrap()).unwrap();
+        assert!(!fs::read_to_string(&index_file).unwrap().contains("zanzibar"));
+
+        store.lock();
+    }
+
+    #[test]
+    fn test_restore_encrypted_memo_while_locked() {
+        let dir = TempDir::new("encryption-trash");
+        let store = dir.store("memos");
+        let memo = store.import("Diary.md", "dear diary").unwrap();
+
+        store.unlock("correct horse").unwrap();
+        let filename = store.encrypt_memo(&MemoPath::parse(&memo.filename).unwrap()).unwrap();
+        store.delete(&MemoPath::parse(&filename).unwrap()).unwrap();
+        store.lock();
+
+        let trashed = store.list_trash().unwrap();
+        let restored = store.restore(&trashed[0].id).unwrap();
+        assert_eq!(restored.filename, filename);
+        assert!(restored.locked);
+        assert_eq!((restored.title.as_str(), restored.content.as_str()), ("", ""));
+
+        store.unlock("correct horse").unwrap();
+        assert_eq!(store.read(&MemoPath::parse(&filename).unwrap(), false).unwrap().content, "dear diary");
+        store.lock();
+    }
+}
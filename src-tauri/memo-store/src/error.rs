@@ -0,0 +1,183 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io;
+use std::path::Path;
+
+/// Error returned by the store and the commands built on it. It serializes as
+/// `{ "kind": "io", "message": "...", "path": "...", "ioKind": "permissionDenied" }`
+/// so the UI can react to the kind and still show `message` as is.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum MemoError {
+    /// A memo, folder, vault or version that doesn't exist
+    NotFound { message: String, path: Option<String> },
+    /// Something with that name exists already
+    AlreadyExists { message: String, path: Option<String> },
+    /// A name or value passed in can't be used, e.g. an invalid folder name
+    InvalidInput { message: String },
+    /// Reading or writing a file failed
+    Io {
+        message: String,
+        path: Option<String>,
+        #[serde(rename = "ioKind")]
+        io_kind: IoErrorKind,
+    },
+    /// A file could not be parsed or data could not be serialized
+    Format { message: String, path: Option<String> },
+    /// The memo is encrypted and the vault has not been unlocked
+    Locked { message: String, path: Option<String> },
+    /// The memo changed on disk since the caller read it. `currentContent` is
+    /// what is there now, None if it was deleted, so the UI can merge or overwrite.
+    Conflict {
+        message: String,
+        path: Option<String>,
+        #[serde(rename = "currentContent")]
+        current_content: Option<String>,
+        #[serde(rename = "currentVersion")]
+        current_version: Option<String>,
+    },
+    /// Errors from code that only reports text
+    Other { message: String },
+}
+
+/// Cause of an I/O error, the part of `std::io::ErrorKind` the UI cares about
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum IoErrorKind {
+    NotFound,
+    PermissionDenied,
+    AlreadyExists,
+    StorageFull,
+    InvalidData,
+    Other,
+}
+
+impl IoErrorKind {
+    fn of(error: &io::Error) -> IoErrorKind {
+        if is_storage_full(error) {
+            return IoErrorKind::StorageFull;
+        }
+        match error.kind() {
+            io::ErrorKind::NotFound => IoErrorKind::NotFound,
+            io::ErrorKind::PermissionDenied => IoErrorKind::PermissionDenied,
+            io::ErrorKind::AlreadyExists => IoErrorKind::AlreadyExists,
+            io::ErrorKind::InvalidData => IoErrorKind::InvalidData,
+            _ => IoErrorKind::Other,
+        }
+    }
+}
+
+/// `io::ErrorKind::StorageFull` is newer than our MSRV, so look at the OS error
+fn is_storage_full(error: &io::Error) -> bool {
+    #[cfg(unix)]
+    const STORAGE_FULL: &[i32] = &[28]; // ENOSPC
+    #[cfg(windows)]
+    const STORAGE_FULL: &[i32] = &[39, 112]; // ERROR_HANDLE_DISK_FULL, ERROR_DISK_FULL
+    #[cfg(not(any(unix, windows)))]
+    const STORAGE_FULL: &[i32] = &[];
+
+    error.raw_os_error().map_or(false, |code| STORAGE_FULL.contains(&code))
+}
+
+fn display_path(path: &Path) -> Option<String> {
+    Some(path.to_string_lossy().to_string())
+}
+
+impl MemoError {
+    /// An I/O error while `context` ("Failed to write memo") on `path`
+    pub fn io(context: &str, path: &Path, error: io::Error) -> MemoError {
+        MemoError::Io {
+            message: format!("{}: {}", context, error),
+            path: display_path(path),
+            io_kind: IoErrorKind::of(&error),
+        }
+    }
+
+    pub fn not_found(message: String, path: &Path) -> MemoError {
+        MemoError::NotFound { message, path: display_path(path) }
+    }
+
+    pub fn already_exists(message: String, path: &Path) -> MemoError {
+        MemoError::AlreadyExists { message, path: display_path(path) }
+    }
+
+    pub fn locked(message: String, path: &Path) -> MemoError {
+        MemoError::Locked { message, path: display_path(path) }
+    }
+
+    pub fn conflict(message: String, path: &Path, current_content: Option<String>, current_version: Option<String>) -> MemoError {
+        MemoError::Conflict {
+            message,
+            path: display_path(path),
+            current_content,
+            current_version,
+        }
+    }
+
+    pub fn invalid_input(message: String) -> MemoError {
+        MemoError::InvalidInput { message }
+    }
+
+    /// A parse or serialize error while `context` on `path`, if there is one
+    pub fn format(context: &str, path: Option<&Path>, error: impl fmt::Display) -> MemoError {
+        MemoError::Format {
+            message: format!("{}: {}", context, error),
+            path: path.and_then(display_path),
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        match self {
+            MemoError::NotFound { message, .. }
+            | MemoError::AlreadyExists { message, .. }
+            | MemoError::InvalidInput { message }
+            | MemoError::Io { message, .. }
+            | MemoError::Format { message, .. }
+            | MemoError::Locked { message, .. }
+            | MemoError::Conflict { message, .. }
+            | MemoError::Other { message } => message,
+        }
+    }
+}
+
+impl fmt::Display for MemoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.message())
+    }
+}
+
+impl std::error::Error for MemoError {}
+
+impl From<String> for MemoError {
+    fn from(message: String) -> MemoError {
+        MemoError::Other { message }
+    }
+}
+
+impl From<MemoError> for String {
+    fn from(error: MemoError) -> String {
+        error.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_io_error_serializes_with_kind_and_path() {
+        let error = MemoError::io(
+            "Failed to write memo",
+            Path::new("/memos/plan.md"),
+            io::Error::from(io::ErrorKind::PermissionDenied),
+        );
+        let json = serde_json::to_value(&error).unwrap();
+
+        assert_eq!(json["kind"], "io");
+        assert_eq!(json["ioKind"], "permissionDenied");
+        assert_eq!(json["path"], "/memos/plan.md");
+        assert!(error.to_string().starts_with("Failed to write memo: "));
+        #[cfg(unix)]
+        assert_eq!(IoErrorKind::of(&io::Error::from_raw_os_error(28)), IoErrorKind::StorageFull);
+    }
+}
@@ -0,0 +1,389 @@
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use pulldown_cmark::escape::{escape_href, escape_html};
+use pulldown_cmark::{html, CowStr, Event, Options, Parser, Tag};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+
+use crate::error::MemoError;
+use crate::store::{MemoMetadata, MemoStore};
+use crate::utils::atomic::atomic_write;
+use crate::utils::links::{link_key, replace_links};
+use crate::utils::paths::{ensure_dir_exists, memo_folder, memo_title, normalize_folder, MemoPath};
+
+/// Stylesheet embedded in every exported page. The print rules make the
+/// browser's "Print to PDF" produce a clean document.
+const STYLE: &str = r#"
+:root { color-scheme: light; }
+body {
+  max-width: 46em;
+  margin: 2em auto;
+  padding: 0 1.5em;
+  font-family: -apple-system, BlinkMacSystemFont, "Segoe UI", "Hiragino Sans", "Noto Sans JP", "Yu Gothic", sans-serif;
+  line-height: 1.7;
+  color: #222;
+}
+h1, h2, h3, h4 { line-height: 1.3; margin: 1.6em 0 0.6em; }
+h1 { font-size: 1.9em; border-bottom: 1px solid #ddd; padding-bottom: 0.3em; }
+a { color: #0b62c4; }
+img { max-width: 100%; }
+pre, code { font-family: "SFMono-Regular", Menlo, Consolas, monospace; font-size: 0.92em; }
+pre { background: #f5f5f5; padding: 0.8em 1em; overflow-x: auto; border-radius: 4px; }
+pre.plain { background: none; padding: 0; white-space: pre-wrap; font-family: inherit; font-size: 1em; }
+:not(pre) > code { background: #f2f2f2; padding: 0.1em 0.3em; border-radius: 3px; }
+blockquote { margin: 1em 0; padding: 0 1em; color: #555; border-left: 4px solid #ddd; }
+table { border-collapse: collapse; margin: 1em 0; }
+th, td { border: 1px solid #ccc; padding: 0.4em 0.7em; }
+th { background: #f5f5f5; }
+nav { font-size: 0.9em; margin-bottom: 1em; }
+ul.memos { list-style: none; padding: 0; }
+ul.memos li { display: flex; justify-content: space-between; gap: 1em; padding: 0.3em 0; border-bottom: 1px solid #eee; }
+ul.memos time { color: #888; white-space: nowrap; }
+@page { margin: 20mm; }
+@media print {
+  body { max-width: none; margin: 0; padding: 0; font-size: 11pt; }
+  nav { display: none; }
+  a { color: inherit; }
+  pre, blockquote, table, img { page-break-inside: avoid; }
+  h1, h2, h3, h4 { page-break-after: avoid; }
+}
+"#;
+
+/// What `export_site` wrote
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportedSite {
+    /// Path of the site's index page
+    pub index: String,
+    /// Number of memo pages written
+    pub pages: usize,
+    /// Encrypted memos that were left out
+    pub skipped: Vec<String>,
+}
+
+/// File types that are inlined as images
+fn image_mime_type(path: &Path) -> Option<&'static str> {
+    let ext = path.extension()?.to_str()?.to_lowercase();
+    match ext.as_str() {
+        "png" => Some("image/png"),
+        "jpg" | "jpeg" => Some("image/jpeg"),
+        "gif" => Some("image/gif"),
+        "webp" => Some("image/webp"),
+        "svg" => Some("image/svg+xml"),
+        "bmp" => Some("image/bmp"),
+        "avif" => Some("image/avif"),
+        _ => None,
+    }
+}
+
+/// Decode `%20` and friends in a link destination
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            if let Some(Ok(byte)) = text.get(i + 1..i + 3).map(|hex| u8::from_str_radix(hex, 16)) {
+                decoded.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+
+    String::from_utf8_lossy(&decoded).to_string()
+}
+
+/// Escape text for use in HTML
+fn escape(text: &str) -> String {
+    let mut escaped = String::new();
+    let _ = escape_html(&mut escaped, text);
+    escaped
+}
+
+/// Escape a relative URL for an `href` attribute
+fn href(url: &str) -> String {
+    let mut escaped = String::new();
+    let _ = escape_href(&mut escaped, url);
+    escaped
+}
+
+/// Paths of the memos' pages in an exported site: `work/plan.md` ->
+/// `work/plan.html`. Memos whose names differ only in the extension keep it,
+/// e.g. `Notes.md.html` and `Notes.txt.html`, and so does a memo that would
+/// take the place of the site's `index.html`.
+fn page_paths<'a>(filenames: impl Iterator<Item = &'a str> + Clone) -> HashMap<String, String> {
+    let stem = |filename: &'a str| filename.rsplit_once('.').map_or(filename, |(stem, _)| stem);
+    // Case-insensitive filesystems would put clashing pages in one file
+    let mut stems: HashMap<String, usize> = HashMap::new();
+    for filename in filenames.clone() {
+        *stems.entry(stem(filename).to_lowercase()).or_insert(0) += 1;
+    }
+
+    filenames
+        .map(|filename| {
+            let key = stem(filename).to_lowercase();
+            let page = if stems[&key] > 1 || key == "index" {
+                format!("{}.html", filename)
+            } else {
+                format!("{}.html", stem(filename))
+            };
+            (filename.to_string(), page)
+        })
+        .collect()
+}
+
+/// Prefix leading from a page back to the root of the site
+fn root_prefix(page: &str) -> String {
+    "../".repeat(page.matches('/').count())
+}
+
+/// A complete HTML document with the stylesheet embedded
+fn render_document(title: &str, nav: Option<&str>, body: &str) -> String {
+    let nav = nav.map(|nav| format!("<nav>{}</nav>\n", nav)).unwrap_or_default();
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+         <meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n\
+         <title>{title}</title>\n<style>{style}</style>\n</head>\n<body>\n{nav}<article>\n<h1>{title}</h1>\n{body}</article>\n</body>\n</html>\n",
+        title = escape(title),
+        style = STYLE,
+        nav = nav,
+        body = body,
+    )
+}
+
+impl MemoStore {
+    /// Turn an image path relative to the memo into a `data:` URL. Remote
+    /// images and files outside the memo directory are left alone.
+    fn inline_image(&self, filename: &str, destination: &str) -> Option<String> {
+        if destination.contains(':') || destination.starts_with('/') || destination.starts_with('#') {
+            return None;
+        }
+
+        let path = self.root().join(memo_folder(filename)).join(percent_decode(destination));
+        let mime_type = image_mime_type(&path)?;
+        let path = path.canonicalize().ok()?;
+        if !path.starts_with(self.root().canonicalize().ok()?) {
+            return None;
+        }
+
+        let bytes = fs::read(&path).ok()?;
+        Some(format!("data:{};base64,{}", mime_type, BASE64.encode(bytes)))
+    }
+
+    /// Render a memo's body to HTML. `[[links]]` become links where `link_href`
+    /// knows a page for their target and plain text otherwise.
+    fn render_memo_body(&self, memo: &MemoMetadata, link_href: impl Fn(&str) -> Option<String>) -> String {
+        if !self.is_markdown_memo(&memo.filename) {
+            return format!("<pre class=\"plain\">{}</pre>\n", escape(&memo.content));
+        }
+
+        let markdown = replace_links(&memo.content, |target, label| match link_href(target) {
+            Some(url) => format!("[{}](<{}>)", label, url),
+            None => label.to_string(),
+        });
+
+        let options = Options::ENABLE_TABLES
+            | Options::ENABLE_FOOTNOTES
+            | Options::ENABLE_STRIKETHROUGH
+            | Options::ENABLE_TASKLISTS;
+        let events = Parser::new_ext(&markdown, options).map(|event| match event {
+            Event::Start(Tag::Image(kind, destination, title)) => {
+                let destination = self
+                    .inline_image(&memo.filename, &destination)
+                    .map(CowStr::from)
+                    .unwrap_or(destination);
+                Event::Start(Tag::Image(kind, destination, title))
+            }
+            event => event,
+        });
+
+        let mut body = String::new();
+        html::push_html(&mut body, events);
+        body
+    }
+
+    /// Render a memo to a standalone HTML document, with its styles embedded
+    /// and local images inlined
+    pub fn render_html(&self, filename: &MemoPath) -> Result<String, MemoError> {
+        let memo = self.read(filename, false)?;
+        let body = self.render_memo_body(&memo, |_| None);
+        Ok(render_document(&memo.title, None, &body))
+    }
+
+    /// Write a memo as a standalone HTML document to `target`
+    pub fn export_memo(&self, filename: &MemoPath, target: &Path) -> Result<(), MemoError> {
+        let document = self.render_html(filename)?;
+        atomic_write(target, document).map_err(|e| MemoError::io("Failed to write export", target, e))
+    }
+
+    /// Export the memos of a folder (with its subfolders), of a tag, or all of
+    /// them to a static site in `target_dir`: one page per memo, laid out like
+    /// the memo directory, and an `index.html` listing them. Links between
+    /// exported memos keep working. Encrypted memos are never exported here.
+    pub fn export_site(&self, folder: Option<&str>, tag: Option<&str>, target_dir: &Path) -> Result<ExportedSite, MemoError> {
+        let mut memos = match tag {
+            Some(tag) => self.memos_by_tag(tag)?,
+            None => self.list()?,
+        };
+        if let Some(folder) = folder {
+            let folder = normalize_folder(folder)?;
+            if !folder.is_empty() {
+                let prefix = format!("{}/", folder);
+                memos.retain(|memo| memo.folder == folder || memo.folder.starts_with(&prefix));
+            }
+        }
+
+        let (skipped, memos): (Vec<MemoMetadata>, Vec<MemoMetadata>) =
+            memos.into_iter().partition(|memo| memo.encrypted);
+
+        let page_of = page_paths(memos.iter().map(|memo| memo.filename.as_str()));
+
+        // Pages reachable by link: by title, by path and by alias
+        let mut pages: HashMap<String, String> = HashMap::new();
+        for memo in &memos {
+            let page = page_of[&memo.filename].clone();
+            let aliases = memo.front_matter.iter().flat_map(|f| f.aliases.iter().map(String::as_str));
+            for key in [memo_title(&memo.filename), memo.filename.as_str()].into_iter().chain(aliases) {
+                pages.entry(link_key(key)).or_insert_with(|| page.clone());
+            }
+        }
+
+        ensure_dir_exists(&target_dir.to_path_buf())?;
+        let mut folders: BTreeMap<&str, Vec<(&MemoMetadata, String)>> = BTreeMap::new();
+
+        for memo in &memos {
+            let page = page_of[&memo.filename].clone();
+            let prefix = root_prefix(&page);
+            let body = self.render_memo_body(memo, |target| {
+                let key = link_key(target);
+                pages.get(&key).map(|linked| format!("{}{}", prefix, linked))
+            });
+            let nav = format!("<a href=\"{}index.html\">← Index</a>", href(&prefix));
+            let document = render_document(&memo.title, Some(&nav), &body);
+
+            let path = target_dir.join(&page);
+            if let Some(parent) = path.parent() {
+                ensure_dir_exists(&parent.to_path_buf())?;
+            }
+            atomic_write(&path, document).map_err(|e| MemoError::io("Failed to write export", &path, e))?;
+
+            folders.entry(memo.folder.as_str()).or_default().push((memo, page));
+        }
+
+        let mut index = String::new();
+        for (folder, entries) in &folders {
+            if !folder.is_empty() {
+                index.push_str(&format!("<h2>{}</h2>\n", escape(folder)));
+            }
+            index.push_str("<ul class=\"memos\">\n");
+            for (memo, page) in entries {
+                index.push_str(&format!(
+                    "<li><a href=\"{}\">{}</a><time>{}</time></li>\n",
+                    href(page),
+                    escape(&memo.title),
+                    escape(memo.updated_at.get(..10).unwrap_or(&memo.updated_at)),
+                ));
+            }
+            index.push_str("</ul>\n");
+        }
+
+        let title = match (tag, folder) {
+            (Some(tag), _) => format!("#{}", tag.trim_start_matches('#')),
+            (None, Some(folder)) if !folder.trim_matches('/').is_empty() => folder.trim_matches('/').to_string(),
+            _ => "Memos".to_string(),
+        };
+        let index_path = target_dir.join("index.html");
+        atomic_write(&index_path, render_document(&title, None, &index))
+            .map_err(|e| MemoError::io("Failed to write export", &index_path, e))?;
+
+        Ok(ExportedSite {
+            index: index_path.to_string_lossy().to_string(),
+            pages: memos.len(),
+            skipped: skipped.into_iter().map(|memo| memo.filename).collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::TempDir;
+
+    #[test]
+    fn test_percent_decode() {
+        assert_eq!(percent_decode("my%20photo.png"), "my photo.png");
+        assert_eq!(percent_decode("%E5%86%99%E7%9C%9F.png"), "写真.png");
+        assert_eq!(percent_decode("100%.png"), "100%.png");
+    }
+
+    #[test]
+    fn test_export_site_links_pages_and_inlines_images() {
+        let dir = TempDir::new("export");
+        let store = dir.store("memos");
+
+        store.import("Plan.md", "See [[work/Notes|the notes]] and [[Missing]].").unwrap();
+        store.create_folder("work").unwrap();
+        let notes = store.create("md", "work").unwrap();
+        store
+            .save("Notes", "# Notes\n\n![chart](chart%201.png)\n", Some(&MemoPath::parse(&notes.filename).unwrap()), None, false, None)
+            .unwrap();
+        fs::write(store.root().join("work").join("chart 1.png"), [0x89, b'P', b'N', b'G']).unwrap();
+
+        let site = store.export_site(None, None, &dir.join("site")).unwrap();
+        assert_eq!(site.pages, 2);
+
+        let plan = fs::read_to_string(dir.join("site").join("Plan.html")).unwrap();
+        assert!(plan.contains("<a href=\"work/Notes.html\">the notes</a>"));
+        assert!(plan.contains("Missing") && !plan.contains("[[Missing]]"));
+
+        let notes = fs::read_to_string(dir.join("site").join("work").join("Notes.html")).unwrap();
+        assert!(notes.contains("src=\"data:image/png;base64,iVBORw==\""));
+        assert!(notes.contains("href=\"../index.html\""));
+
+        let index = fs::read_to_string(&site.index).unwrap();
+        assert!(index.contains("<a href=\"work/Notes.html\">Notes</a>"));
+    }
+
+    #[test]
+    fn test_export_site_keeps_memos_with_the_same_title_apart() {
+        let dir = TempDir::new("export-clash");
+        let store = dir.store("memos");
+
+        store.import("Notes.md", "markdown notes").unwrap();
+        store.import("Notes.txt", "plain notes").unwrap();
+        store.import("index.md", "my index").unwrap();
+        store.import("Plan.md", "plan").unwrap();
+
+        let site = store.export_site(None, None, &dir.join("site")).unwrap();
+        assert_eq!(site.pages, 4);
+
+        let site_dir = dir.join("site");
+        assert!(fs::read_to_string(site_dir.join("Notes.md.html")).unwrap().contains("markdown notes"));
+        assert!(fs::read_to_string(site_dir.join("Notes.txt.html")).unwrap().contains("plain notes"));
+        assert!(fs::read_to_string(site_dir.join("index.md.html")).unwrap().contains("my index"));
+        assert!(site_dir.join("Plan.html").exists());
+
+        let index = fs::read_to_string(&site.index).unwrap();
+        assert!(index.contains("href=\"Notes.md.html\"") && index.contains("href=\"Notes.txt.html\""));
+    }
+
+    #[test]
+    fn test_encrypted_text_memo_is_not_rendered_as_markdown() {
+        let dir = TempDir::new("export-encrypted");
+        let store = dir.store("memos");
+        let memo = store.import("Todo.txt", "# not a heading").unwrap();
+
+        store.unlock("correct horse").unwrap();
+        let filename = store.encrypt_memo(&MemoPath::parse(&memo.filename).unwrap()).unwrap();
+        let html = store.render_html(&MemoPath::parse(&filename).unwrap()).unwrap();
+        assert!(html.contains("<pre class=\"plain\"># not a heading</pre>"));
+
+        store.lock();
+    }
+}
@@ -0,0 +1,147 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use crate::error::MemoError;
+use crate::store::MemoStore;
+use crate::utils::paths::{ensure_dir_exists, normalize_folder, relative_memo_path};
+
+/// Collect folders below the memo directory, skipping hidden ones such as `.trash`
+fn walk_folders(memo_dir: &Path) -> Result<Vec<String>, MemoError> {
+    let mut folders = Vec::new();
+    let mut pending = vec![memo_dir.to_path_buf()];
+
+    while let Some(current) = pending.pop() {
+        let entries = fs::read_dir(&current)
+            .map_err(|e| MemoError::io("Failed to read memo directory", &current, e))?;
+
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if !entry.file_type().map(|t| t.is_dir()).unwrap_or(false) {
+                continue;
+            }
+            if let Some(folder) = relative_memo_path(memo_dir, &path) {
+                folders.push(folder);
+                pending.push(path);
+            }
+        }
+    }
+
+    folders.sort();
+    Ok(folders)
+}
+
+/// Point keys under `old_folder/` at `new_folder/`; returns whether anything changed
+fn rekey_folder<V>(map: &mut HashMap<String, V>, old_folder: &str, new_folder: &str) -> bool {
+    let prefix = format!("{}/", old_folder);
+    let moved: Vec<String> = map.keys().filter(|key| key.starts_with(&prefix)).cloned().collect();
+
+    for key in &moved {
+        if let Some(value) = map.remove(key) {
+            map.insert(format!("{}/{}", new_folder, &key[prefix.len()..]), value);
+        }
+    }
+    !moved.is_empty()
+}
+
+impl MemoStore {
+    /// List every folder in the memo directory as a relative path, e.g. `work/project-x`
+    pub fn list_folders(&self) -> Result<Vec<String>, MemoError> {
+        walk_folders(self.root())
+    }
+
+    /// Create a folder (and any missing parents) and return its normalized path
+    pub fn create_folder(&self, path: &str) -> Result<String, MemoError> {
+        let memo_dir = self.root();
+        let folder = normalize_folder(path)?;
+        if folder.is_empty() {
+            return Err(MemoError::invalid_input("Folder name is empty".to_string()));
+        }
+
+        ensure_dir_exists(&memo_dir.join(&folder))?;
+        Ok(folder)
+    }
+
+    /// Rename or move a folder, carrying pins, order and history of the memos in it
+    pub fn rename_folder(&self, old_path: &str, new_path: &str) -> Result<String, MemoError> {
+        let memo_dir = self.root();
+        let old_folder = normalize_folder(old_path)?;
+        let new_folder = normalize_folder(new_path)?;
+
+        if old_folder.is_empty() || new_folder.is_empty() {
+            return Err(MemoError::invalid_input("Folder name is empty".to_string()));
+        }
+        if old_folder == new_folder {
+            return Ok(new_folder);
+        }
+        if new_folder.starts_with(&format!("{}/", old_folder)) {
+            return Err(MemoError::invalid_input(format!("Cannot move folder '{}' into itself", old_folder)));
+        }
+
+        let old_dir = memo_dir.join(&old_folder);
+        let new_dir = memo_dir.join(&new_folder);
+        if !old_dir.is_dir() {
+            return Err(MemoError::not_found(format!("Folder '{}' not found", old_folder), &old_dir));
+        }
+        if new_dir.exists() {
+            return Err(MemoError::already_exists(format!("Folder '{}' already exists", new_folder), &new_dir));
+        }
+
+        if let Some(parent) = new_dir.parent() {
+            ensure_dir_exists(&parent.to_path_buf())?;
+        }
+        self.mark_write(&old_dir);
+        self.mark_write(&new_dir);
+        fs::rename(&old_dir, &new_dir)
+            .map_err(|e| MemoError::io("Failed to rename folder", &old_dir, e))?;
+
+        let prefix = format!("{}/", old_folder);
+        self.rename_memo_ids(|filename| {
+            filename
+                .strip_prefix(&prefix)
+                .map(|rest| format!("{}/{}", new_folder, rest))
+        })?;
+
+        // Entries written before memos had IDs are still keyed by filename
+        let mut pins = self.read_pin_data().unwrap_or_default();
+        if rekey_folder(&mut pins, &old_folder, &new_folder) {
+            self.write_pin_data(&pins)?;
+        }
+
+        let mut order = self.read_order_data().unwrap_or_default();
+        if rekey_folder(&mut order, &old_folder, &new_folder) {
+            self.write_order_data(&order)?;
+        }
+
+        // History is a safety net; failing to move it must not undo the rename
+        let history_dir = memo_dir.join(".history");
+        let old_history = history_dir.join(&old_folder);
+        let new_history = history_dir.join(&new_folder);
+        if old_history.exists() && !new_history.exists() {
+            if let Some(parent) = new_history.parent() {
+                let _ = ensure_dir_exists(&parent.to_path_buf());
+            }
+            let _ = fs::rename(&old_history, &new_history);
+        }
+
+        Ok(new_folder)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rekey_folder() {
+        let mut pins = HashMap::new();
+        pins.insert("work/a.md".to_string(), 1);
+        pins.insert("work/sub/b.md".to_string(), 2);
+        pins.insert("workshop/c.md".to_string(), 3);
+
+        assert!(rekey_folder(&mut pins, "work", "archive/work"));
+        assert_eq!(pins.get("archive/work/a.md"), Some(&1));
+        assert_eq!(pins.get("archive/work/sub/b.md"), Some(&2));
+        assert_eq!(pins.get("workshop/c.md"), Some(&3));
+    }
+}
@@ -0,0 +1,460 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output};
+
+use crate::error::MemoError;
+use crate::store::{conflict_copy_filename, MemoStore};
+use crate::utils::atomic::atomic_write;
+use crate::utils::paths::{is_encrypted_memo, is_memo_file, memo_title, MemoPath};
+
+/// Written to new repositories. Derived files stay out of git, and so do the
+/// trash and version history: git keeps the history of a git-backed vault.
+const GITIGNORE: &str = ".search-index.json
+.sync-state.json
+.memo-fingerprints.json
+*.bak
+.*.tmp
+.trash/
+.history/
+";
+
+/// Identity for commits on machines where git has none configured
+const FALLBACK_IDENTITY: &[&str] = &["-c", "user.name=RakuMemo", "-c", "user.email=rakumemo@localhost"];
+
+/// Remote pulled from and pushed to while the branch has no upstream yet
+const DEFAULT_REMOTE: &str = "origin";
+
+/// State of the repository in the memo directory
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GitStatus {
+    pub is_repository: bool,
+    /// None on a detached HEAD
+    pub branch: Option<String>,
+    pub upstream: Option<String>,
+    pub ahead: usize,
+    pub behind: usize,
+    pub changes: Vec<GitChange>,
+    /// Files with unresolved merge conflicts
+    pub conflicts: Vec<String>,
+}
+
+/// A changed file, relative to the memo directory
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GitChange {
+    pub path: String,
+    /// Two-letter status as shown by `git status --short`, e.g. ` M` or `??`
+    pub status: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GitCommit {
+    pub hash: String,
+    pub author: String,
+    pub date: String,
+    pub message: String,
+}
+
+/// A memo changed on both sides of a pull. The local version stays in place
+/// and the remote one is written next to it as a conflict copy.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GitConflict {
+    pub filename: String,
+    /// Local content; None when deleted locally or encrypted
+    pub ours: Option<String>,
+    /// Remote content; None when deleted remotely or encrypted
+    pub theirs: Option<String>,
+    pub conflict_copy: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GitPullResult {
+    /// Whether the pull brought in any commits
+    pub updated: bool,
+    pub conflicts: Vec<GitConflict>,
+}
+
+const UNMERGED_STATUSES: &[&str] = &["DD", "AU", "UD", "UA", "DU", "AA", "UU"];
+
+/// Parse the `## branch...upstream [ahead 1, behind 2]` line of `git status -b`
+fn parse_branch_line(line: &str) -> (Option<String>, Option<String>, usize, usize) {
+    let line = line.trim_start_matches("## ");
+    let (names, tracking) = match line.split_once(" [") {
+        Some((names, tracking)) => (names, tracking.trim_end_matches(']')),
+        None => (line, ""),
+    };
+    let names = names
+        .strip_prefix("No commits yet on ")
+        .or_else(|| names.strip_prefix("Initial commit on "))
+        .unwrap_or(names);
+
+    let (branch, upstream) = match names.split_once("...") {
+        Some((branch, upstream)) => (branch, Some(upstream.to_string())),
+        None => (names, None),
+    };
+    let branch = (!branch.starts_with("HEAD (")).then(|| branch.to_string());
+
+    let mut ahead = 0;
+    let mut behind = 0;
+    for part in tracking.split(", ") {
+        if let Some(count) = part.strip_prefix("ahead ") {
+            ahead = count.parse().unwrap_or(0);
+        } else if let Some(count) = part.strip_prefix("behind ") {
+            behind = count.parse().unwrap_or(0);
+        }
+    }
+
+    (branch, upstream, ahead, behind)
+}
+
+/// Commit message naming the memos in a commit, e.g. "Update Plan, Notes and 2 more"
+fn commit_message(files: &[&str]) -> String {
+    let titles: Vec<&str> = files
+        .iter()
+        .filter(|file| !file.starts_with('.') && is_memo_file(Path::new(file)) && !is_encrypted_memo(file))
+        .map(|file| memo_title(file))
+        .collect();
+
+    match titles.len() {
+        0 if files.iter().any(|file| is_encrypted_memo(file)) => "Update encrypted memos".to_string(),
+        0 => "Update vault metadata".to_string(),
+        1..=3 => format!("Update {}", titles.join(", ")),
+        n => format!("Update {} and {} more", titles[..2].join(", "), n - 2),
+    }
+}
+
+fn git_error(command: &str, output: &Output) -> MemoError {
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let detail = if stderr.trim().is_empty() { stdout.trim() } else { stderr.trim() };
+    MemoError::Other {
+        message: format!("git {} failed: {}", command, detail),
+    }
+}
+
+impl MemoStore {
+    /// Run git in the memo directory. Credentials are never prompted for, so
+    /// a remote that needs them fails instead of hanging.
+    fn git_command(&self, args: &[&str]) -> Result<Output, MemoError> {
+        Command::new("git")
+            .args(args)
+            .current_dir(self.root())
+            .env("GIT_TERMINAL_PROMPT", "0")
+            .output()
+            .map_err(|e| MemoError::io("Failed to run git", self.root(), e))
+    }
+
+    /// Run git and return its output, or an error with git's message
+    fn git(&self, args: &[&str]) -> Result<String, MemoError> {
+        let output = self.git_command(args)?;
+        if !output.status.success() {
+            return Err(git_error(args[0], &output));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    }
+
+    /// Run a git command that creates commits, with a fallback identity when
+    /// git has none configured
+    fn git_committing(&self, args: &[&str]) -> Result<Output, MemoError> {
+        let has_identity = self.git(&["config", "user.email"]).map_or(false, |email| !email.trim().is_empty());
+        let mut full_args: Vec<&str> = if has_identity { Vec::new() } else { FALLBACK_IDENTITY.to_vec() };
+        full_args.extend_from_slice(args);
+        self.git_command(&full_args)
+    }
+
+    fn git_head(&self) -> Option<String> {
+        self.git(&["rev-parse", "--verify", "-q", "HEAD"]).ok().map(|head| head.trim().to_string())
+    }
+
+    /// Whether the memo directory is the root of a git repository
+    pub fn is_git_repository(&self) -> bool {
+        self.root().join(".git").exists()
+    }
+
+    /// Turn the memo directory into a git repository, if it isn't one already,
+    /// and give it a `.gitignore` for the app's derived files
+    pub fn git_init(&self) -> Result<(), MemoError> {
+        if !self.is_git_repository() {
+            self.git(&["init", "-q"])?;
+        }
+
+        let gitignore = self.root().join(".gitignore");
+        if !gitignore.exists() {
+            atomic_write(&gitignore, GITIGNORE).map_err(|e| MemoError::io("Failed to write .gitignore", &gitignore, e))?;
+        }
+        Ok(())
+    }
+
+    /// Point the `origin` remote at `url`, e.g. a path or a `file://` URL
+    pub fn git_set_remote(&self, url: &str) -> Result<(), MemoError> {
+        if self.git(&["remote", "get-url", DEFAULT_REMOTE]).is_ok() {
+            self.git(&["remote", "set-url", DEFAULT_REMOTE, url])?;
+        } else {
+            self.git(&["remote", "add", DEFAULT_REMOTE, url])?;
+        }
+        Ok(())
+    }
+
+    pub fn git_status(&self) -> Result<GitStatus, MemoError> {
+        if !self.is_git_repository() {
+            return Ok(GitStatus::default());
+        }
+
+        let output = self.git(&["status", "--porcelain=v1", "-b", "-z"])?;
+        let mut status = GitStatus {
+            is_repository: true,
+            ..GitStatus::default()
+        };
+
+        let mut records = output.split('\0').filter(|record| !record.is_empty());
+        while let Some(record) = records.next() {
+            if record.starts_with("## ") {
+                let (branch, upstream, ahead, behind) = parse_branch_line(record);
+                status.branch = branch;
+                status.upstream = upstream;
+                status.ahead = ahead;
+                status.behind = behind;
+                continue;
+            }
+
+            let (code, path) = match (record.get(..2), record.get(3..)) {
+                (Some(code), Some(path)) => (code, path.to_string()),
+                _ => continue,
+            };
+            // Renames and copies are followed by the original path
+            if code.starts_with(['R', 'C']) {
+                records.next();
+            }
+            if UNMERGED_STATUSES.contains(&code) {
+                status.conflicts.push(path.clone());
+            }
+            status.changes.push(GitChange {
+                path,
+                status: code.to_string(),
+            });
+        }
+
+        Ok(status)
+    }
+
+    /// Commit everything that changed in the memo directory, with a message
+    /// naming the changed memos. Returns None when there was nothing to commit.
+    pub fn git_commit_changes(&self) -> Result<Option<GitCommit>, MemoError> {
+        self.git(&["add", "-A"])?;
+        let staged = self.git(&["-c", "core.quotepath=false", "diff", "--cached", "--name-only", "-z"])?;
+        let files: Vec<&str> = staged.split('\0').filter(|file| !file.is_empty()).collect();
+        if files.is_empty() {
+            return Ok(None);
+        }
+
+        let message = commit_message(&files);
+        let output = self.git_committing(&["commit", "-q", "-m", &message])?;
+        if !output.status.success() {
+            return Err(git_error("commit", &output));
+        }
+        Ok(self.git_log(None, 1)?.pop())
+    }
+
+    fn git_log(&self, path: Option<&str>, limit: usize) -> Result<Vec<GitCommit>, MemoError> {
+        if self.git_head().is_none() {
+            return Ok(Vec::new());
+        }
+
+        let limit = limit.to_string();
+        let mut args = vec!["log", "-n", &limit, "--format=%H%x1f%an%x1f%aI%x1f%s"];
+        if let Some(path) = path {
+            args.extend_from_slice(&["--follow", "--", path]);
+        }
+
+        Ok(self
+            .git(&args)?
+            .lines()
+            .filter_map(|line| {
+                let mut fields = line.split('\x1f');
+                Some(GitCommit {
+                    hash: fields.next()?.to_string(),
+                    author: fields.next()?.to_string(),
+                    date: fields.next()?.to_string(),
+                    message: fields.next()?.to_string(),
+                })
+            })
+            .collect())
+    }
+
+    /// Commits that touched a memo, newest first, following renames
+    pub fn git_log_for_memo(&self, filename: &MemoPath, limit: usize) -> Result<Vec<GitCommit>, MemoError> {
+        self.git_log(Some(filename.as_str()), limit)
+    }
+
+    /// One side of a conflicted file (`:2:` ours, `:3:` theirs), None if it was deleted there
+    fn git_conflict_side(&self, stage: u8, path: &str) -> Option<Vec<u8>> {
+        let output = self.git_command(&["show", &format!(":{}:{}", stage, path)]).ok()?;
+        output.status.success().then(|| output.stdout)
+    }
+
+    /// Finish a merge that stopped on conflicts without leaving conflict
+    /// markers in any file: local versions win, and remote versions of memos
+    /// are kept as conflict copies next to them
+    fn resolve_pull_conflicts(&self, paths: &[String]) -> Result<Vec<GitConflict>, MemoError> {
+        let label = chrono::Local::now().format("%Y-%m-%d").to_string();
+        let mut conflicts = Vec::new();
+
+        for path in paths {
+            let ours = self.git_conflict_side(2, path);
+            let theirs = self.git_conflict_side(3, path);
+            let file = self.root().join(path);
+
+            match &ours {
+                Some(contents) => atomic_write(&file, contents).map_err(|e| MemoError::io("Failed to write memo", &file, e))?,
+                None if file.exists() => fs::remove_file(&file).map_err(|e| MemoError::io("Failed to delete file", &file, e))?,
+                None => {}
+            }
+
+            // Metadata such as .pins.json simply keeps the local version
+            if path.starts_with('.') || !is_memo_file(Path::new(path)) {
+                continue;
+            }
+
+            let mut conflict_copy = None;
+            if let Some(contents) = theirs.as_ref().filter(|theirs| Some(*theirs) != ours.as_ref()) {
+                let filename = conflict_copy_filename(self.root(), path, &label)?;
+                let copy_path = self.root().join(&filename);
+                atomic_write(&copy_path, contents).map_err(|e| MemoError::io("Failed to write memo", &copy_path, e))?;
+                conflict_copy = Some(filename);
+            }
+
+            let text = |contents: Option<Vec<u8>>| {
+                contents
+                    .filter(|_| !is_encrypted_memo(path))
+                    .and_then(|contents| String::from_utf8(contents).ok())
+            };
+            conflicts.push(GitConflict {
+                filename: path.clone(),
+                ours: text(ours),
+                theirs: text(theirs),
+                conflict_copy,
+            });
+        }
+
+        self.git(&["add", "-A"])?;
+        let output = self.git_committing(&["commit", "-q", "--no-edit"])?;
+        if !output.status.success() {
+            return Err(git_error("commit", &output));
+        }
+        Ok(conflicts)
+    }
+
+    /// Commit local changes, then merge in the remote branch. Conflicting
+    /// memos keep their local content and get the remote one as a conflict
+    /// copy; both versions are returned for the UI to show.
+    pub fn git_pull(&self) -> Result<GitPullResult, MemoError> {
+        self.git_commit_changes()?;
+        let status = self.git_status()?;
+        let before = self.git_head();
+
+        let mut args = vec!["pull", "-q", "--no-rebase", "--no-edit", "--allow-unrelated-histories"];
+        let branch;
+        if status.upstream.is_none() {
+            branch = status
+                .branch
+                .ok_or_else(|| MemoError::Other { message: "Not on a branch".to_string() })?;
+            args.extend_from_slice(&[DEFAULT_REMOTE, &branch]);
+        }
+
+        let output = self.git_committing(&args)?;
+        let conflicts = if output.status.success() {
+            Vec::new()
+        } else {
+            let conflicted = self.git_status()?.conflicts;
+            if conflicted.is_empty() {
+                return Err(git_error("pull", &output));
+            }
+            self.resolve_pull_conflicts(&conflicted)?
+        };
+
+        Ok(GitPullResult {
+            updated: self.git_head() != before,
+            conflicts,
+        })
+    }
+
+    /// Commit local changes and push them, setting the upstream on first push
+    pub fn git_push(&self) -> Result<(), MemoError> {
+        self.git_commit_changes()?;
+        let status = self.git_status()?;
+
+        if status.upstream.is_some() {
+            self.git(&["push", "-q"])?;
+        } else {
+            let branch = status
+                .branch
+                .ok_or_else(|| MemoError::Other { message: "Not on a branch".to_string() })?;
+            self.git(&["push", "-q", "-u", DEFAULT_REMOTE, &branch])?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::TempDir;
+
+    #[test]
+    fn test_parse_branch_line() {
+        assert_eq!(
+            parse_branch_line("## main...origin/main [ahead 1, behind 2]"),
+            (Some("main".to_string()), Some("origin/main".to_string()), 1, 2)
+        );
+        assert_eq!(parse_branch_line("## No commits yet on main"), (Some("main".to_string()), None, 0, 0));
+        assert_eq!(parse_branch_line("## HEAD (no branch)"), (None, None, 0, 0));
+        assert_eq!(commit_message(&["Plan.md", ".pins.json"]), "Update Plan");
+        assert_eq!(commit_message(&["a.md", "b.md", "c.md", "d.md"]), "Update a, b and 2 more");
+    }
+
+    #[test]
+    fn test_pull_keeps_both_sides_of_a_conflict() {
+        if Command::new("git").arg("--version").output().is_err() {
+            return;
+        }
+        let dir = TempDir::new("git");
+        let remote = dir.join("remote.git");
+        Command::new("git").args(["init", "-q", "--bare"]).arg(&remote).output().unwrap();
+        let remote_url = format!("file://{}", remote.display());
+
+        let a = MemoStore::open(dir.join("a")).unwrap();
+        a.git_init().unwrap();
+        a.git_set_remote(&remote_url).unwrap();
+        let plan = a.import("Plan.md", "first").unwrap();
+        let plan = MemoPath::parse(&plan.filename).unwrap();
+        a.git_push().unwrap();
+
+        let b = MemoStore::open(dir.join("b")).unwrap();
+        b.git_init().unwrap();
+        b.git_set_remote(&remote_url).unwrap();
+        assert!(b.git_pull().unwrap().updated);
+        assert_eq!(b.read(&plan, false).unwrap().content, "first");
+
+        a.save("Plan", "from a", Some(&plan), None, false, None).unwrap();
+        a.git_push().unwrap();
+        b.save("Plan", "from b", Some(&plan), None, false, None).unwrap();
+
+        let pulled = b.git_pull().unwrap();
+        assert_eq!(pulled.conflicts.len(), 1);
+        assert_eq!(pulled.conflicts[0].ours.as_deref(), Some("from b"));
+        assert_eq!(pulled.conflicts[0].theirs.as_deref(), Some("from a"));
+        assert_eq!(b.read(&plan, false).unwrap().content, "from b");
+        let copy = MemoPath::parse(pulled.conflicts[0].conflict_copy.as_deref().unwrap()).unwrap();
+        assert_eq!(b.read(&copy, false).unwrap().content, "from a");
+
+        let status = b.git_status().unwrap();
+        assert!(status.conflicts.is_empty() && status.changes.is_empty());
+        assert_eq!(b.git_log_for_memo(&plan, 10).unwrap().len(), 3);
+        b.git_push().unwrap();
+    }
+}
@@ -0,0 +1,313 @@
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use similar::{ChangeTag, TextDiff};
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+use crate::error::MemoError;
+use crate::search;
+use crate::store::{content_hash, MemoMetadata, MemoStore};
+use crate::utils::atomic::atomic_write;
+use crate::utils::paths::{ensure_dir_exists, MemoPath};
+
+/// Minimum time between two snapshots of the same memo during normal editing
+const SNAPSHOT_INTERVAL_MINUTES: i64 = 5;
+
+/// Oldest versions beyond this count are dropped
+const MAX_VERSIONS: usize = 100;
+
+/// Lines of unchanged context around each diff hunk
+const DIFF_CONTEXT_LINES: usize = 3;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MemoVersion {
+    pub id: String,
+    pub created_at: String,
+    pub size: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiffHunk {
+    pub old_start: usize,
+    pub old_lines: usize,
+    pub new_start: usize,
+    pub new_lines: usize,
+    pub lines: Vec<DiffLine>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiffLine {
+    /// "equal", "insert" or "delete"
+    pub kind: String,
+    pub old_line: Option<usize>,
+    pub new_line: Option<usize>,
+    pub content: String,
+}
+
+/// Get the directory holding the versions of one memo
+fn get_history_dir(memo_dir: &Path, filename: &str) -> PathBuf {
+    memo_dir.join(".history").join(filename)
+}
+
+/// Get the version list path of one memo
+fn get_versions_file(memo_dir: &Path, filename: &str) -> PathBuf {
+    get_history_dir(memo_dir, filename).join("versions.json")
+}
+
+/// Read the versions of a memo, oldest first
+fn read_versions(memo_dir: &Path, filename: &str) -> Result<Vec<MemoVersion>, MemoError> {
+    let versions_file = get_versions_file(memo_dir, filename);
+
+    if !versions_file.exists() {
+        return Ok(Vec::new());
+    }
+
+    let content = fs::read_to_string(&versions_file)
+        .map_err(|e| MemoError::io("Failed to read version list", &versions_file, e))?;
+
+    serde_json::from_str(&content).map_err(|e| MemoError::format("Failed to parse version list", Some(&versions_file), e))
+}
+
+/// Write the versions of a memo
+fn write_versions(memo_dir: &Path, filename: &str, versions: &[MemoVersion]) -> Result<(), MemoError> {
+    let versions_file = get_versions_file(memo_dir, filename);
+    let content = serde_json::to_string_pretty(versions)
+        .map_err(|e| MemoError::format("Failed to serialize version list", None, e))?;
+
+    atomic_write(&versions_file, content)
+        .map_err(|e| MemoError::io("Failed to write version list", &versions_file, e))?;
+
+    Ok(())
+}
+
+/// Store `content` as a new compressed version of the memo
+fn write_snapshot(memo_dir: &Path, filename: &str, content: &str) -> Result<(), MemoError> {
+    let history_dir = get_history_dir(memo_dir, filename);
+    ensure_dir_exists(&history_dir)?;
+
+    let mut versions = read_versions(memo_dir, filename)?;
+
+    let now = chrono::Utc::now();
+    let id = now.format("%Y%m%dT%H%M%S%3f").to_string();
+    let path = history_dir.join(format!("{}.gz", id));
+
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder
+        .write_all(content.as_bytes())
+        .map_err(|e| MemoError::io("Failed to compress version", &path, e))?;
+    let compressed = encoder
+        .finish()
+        .map_err(|e| MemoError::io("Failed to compress version", &path, e))?;
+
+    atomic_write(&path, compressed)
+        .map_err(|e| MemoError::io("Failed to write version", &path, e))?;
+
+    versions.push(MemoVersion {
+        id,
+        created_at: now.to_rfc3339(),
+        size: content.len() as u64,
+    });
+
+    if versions.len() > MAX_VERSIONS {
+        let excess = versions.len() - MAX_VERSIONS;
+        for version in versions.drain(..excess) {
+            let _ = fs::remove_file(history_dir.join(format!("{}.gz", version.id)));
+        }
+    }
+
+    write_versions(memo_dir, filename, &versions)
+}
+
+/// Read the content of one stored version
+fn read_snapshot(memo_dir: &Path, filename: &str, id: &str) -> Result<String, MemoError> {
+    // Ids are timestamps; anything else could point outside the history folder
+    if id.is_empty() || !id.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Err(MemoError::invalid_input(format!("Invalid version id: '{}'", id)));
+    }
+
+    let path = get_history_dir(memo_dir, filename).join(format!("{}.gz", id));
+    if !path.exists() {
+        return Err(MemoError::not_found(format!("Version '{}' of memo '{}' not found", id, filename), &path));
+    }
+
+    let compressed = fs::read(&path)
+        .map_err(|e| MemoError::io("Failed to read version", &path, e))?;
+
+    let mut content = String::new();
+    GzDecoder::new(compressed.as_slice())
+        .read_to_string(&mut content)
+        .map_err(|e| MemoError::io("Failed to decompress version", &path, e))?;
+
+    Ok(content)
+}
+
+/// Find the stored version of a memo whose content has the given hash, newest first
+pub(crate) fn find_snapshot(memo_dir: &Path, filename: &str, hash: &str) -> Option<String> {
+    let versions = read_versions(memo_dir, filename).ok()?;
+    versions
+        .iter()
+        .rev()
+        .filter_map(|version| read_snapshot(memo_dir, filename, &version.id).ok())
+        .find(|content| content_hash(content.as_bytes()) == hash)
+}
+
+/// Keep the content a save is about to overwrite.
+///
+/// Snapshots are throttled to one per `SNAPSHOT_INTERVAL_MINUTES` so autosave
+/// doesn't store every keystroke, except when the new content is less than
+/// half the size of the old one: that is what an accidentally cleared buffer
+/// looks like, and it must always be recoverable.
+pub(crate) fn snapshot_before_save(
+    memo_dir: &Path,
+    filename: &str,
+    previous: &str,
+    next: &str,
+) -> Result<(), MemoError> {
+    if previous == next || previous.trim().is_empty() {
+        return Ok(());
+    }
+
+    let versions = read_versions(memo_dir, filename)?;
+    let due = match versions.last() {
+        Some(last) => chrono::DateTime::parse_from_rfc3339(&last.created_at)
+            .map(|created_at| {
+                chrono::Utc::now().signed_duration_since(created_at)
+                    >= chrono::Duration::minutes(SNAPSHOT_INTERVAL_MINUTES)
+            })
+            .unwrap_or(true),
+        None => true,
+    };
+    let shrinking = next.len() < previous.len() / 2;
+
+    if due || shrinking {
+        write_snapshot(memo_dir, filename, previous)?;
+    }
+    Ok(())
+}
+
+/// Move a history directory, unless there is none or `new_dir` is taken
+fn move_history_dir(old_dir: &Path, new_dir: &Path) -> Result<(), MemoError> {
+    if !old_dir.exists() || new_dir.exists() {
+        return Ok(());
+    }
+    if let Some(parent) = new_dir.parent() {
+        ensure_dir_exists(&parent.to_path_buf())?;
+    }
+
+    fs::rename(old_dir, new_dir).map_err(|e| MemoError::io("Failed to move memo history", old_dir, e))
+}
+
+/// Carry a memo's history over when the memo is renamed
+pub(crate) fn rename_history(memo_dir: &Path, old_filename: &str, new_filename: &str) -> Result<(), MemoError> {
+    move_history_dir(&get_history_dir(memo_dir, old_filename), &get_history_dir(memo_dir, new_filename))
+}
+
+/// Move a memo's history out to `target`, e.g. into the trash with the memo
+pub(crate) fn stash_history(memo_dir: &Path, filename: &str, target: &Path) -> Result<(), MemoError> {
+    move_history_dir(&get_history_dir(memo_dir, filename), target)
+}
+
+/// Give a memo back the history moved out with `stash_history`
+pub(crate) fn unstash_history(memo_dir: &Path, source: &Path, filename: &str) -> Result<(), MemoError> {
+    move_history_dir(source, &get_history_dir(memo_dir, filename))
+}
+
+/// Delete a memo's history, e.g. once the memo is encrypted and plain copies must go
+pub(crate) fn remove_history(memo_dir: &Path, filename: &str) -> Result<(), MemoError> {
+    let dir = get_history_dir(memo_dir, filename);
+    if !dir.exists() {
+        return Ok(());
+    }
+
+    fs::remove_dir_all(&dir).map_err(|e| MemoError::io("Failed to delete memo history", &dir, e))
+}
+
+impl MemoStore {
+    /// List the stored versions of a memo, newest first
+    pub fn list_versions(&self, filename: &MemoPath) -> Result<Vec<MemoVersion>, MemoError> {
+        let mut versions = read_versions(self.root(), filename)?;
+        versions.reverse();
+        Ok(versions)
+    }
+
+    /// Diff two versions of a memo; `to` defaults to the current content
+    pub fn diff_versions(&self, filename: &MemoPath, from: &str, to: Option<&str>) -> Result<Vec<DiffHunk>, MemoError> {
+        let memo_dir = self.root();
+
+        let old = read_snapshot(memo_dir, filename, from)?;
+        let new = match to {
+            Some(to) => read_snapshot(memo_dir, filename, to)?,
+            None => {
+                let path = memo_dir.join(filename);
+                fs::read_to_string(&path).map_err(|e| MemoError::io("Failed to read file", &path, e))?
+            }
+        };
+
+        let diff = TextDiff::from_lines(&old, &new);
+        let mut hunks = Vec::new();
+
+        for group in diff.grouped_ops(DIFF_CONTEXT_LINES) {
+            let (first, last) = match (group.first(), group.last()) {
+                (Some(first), Some(last)) => (first, last),
+                _ => continue,
+            };
+
+            let mut lines = Vec::new();
+            for op in &group {
+                for change in diff.iter_changes(op) {
+                    let kind = match change.tag() {
+                        ChangeTag::Equal => "equal",
+                        ChangeTag::Insert => "insert",
+                        ChangeTag::Delete => "delete",
+                    };
+                    lines.push(DiffLine {
+                        kind: kind.to_string(),
+                        old_line: change.old_index().map(|i| i + 1),
+                        new_line: change.new_index().map(|i| i + 1),
+                        content: change.value().trim_end_matches(['\r', '\n']).to_string(),
+                    });
+                }
+            }
+
+            let old_range = first.old_range().start..last.old_range().end;
+            let new_range = first.new_range().start..last.new_range().end;
+            hunks.push(DiffHunk {
+                old_start: old_range.start + 1,
+                old_lines: old_range.len(),
+                new_start: new_range.start + 1,
+                new_lines: new_range.len(),
+                lines,
+            });
+        }
+
+        Ok(hunks)
+    }
+
+    /// Replace a memo's content with an earlier version; the current content is
+    /// kept as a version of its own first, so restoring can be undone
+    pub fn restore_version(&self, filename: &MemoPath, version_id: &str) -> Result<MemoMetadata, MemoError> {
+        let memo_dir = self.root();
+        let file_path = memo_dir.join(filename);
+
+        let restored = read_snapshot(memo_dir, filename, version_id)?;
+        let current = fs::read_to_string(&file_path)
+            .map_err(|e| MemoError::io("Failed to read file", &file_path, e))?;
+
+        if current != restored && !current.trim().is_empty() {
+            write_snapshot(memo_dir, filename, &current)?;
+        }
+
+        self.mark_write(&file_path);
+        atomic_write(&file_path, &restored)
+            .map_err(|e| MemoError::io("Failed to write memo", &file_path, e))?;
+        search::index_memo(memo_dir, filename, &restored);
+
+        self.load(filename, false)
+    }
+}
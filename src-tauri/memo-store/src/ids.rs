@@ -0,0 +1,348 @@
+//! Stable memo IDs. `.memo-ids.json` maps each ID to the memo's filename and
+//! pins and the sidebar order are stored by ID, so they stay with a memo when
+//! its file is renamed, whether by the store or by another program.
+//! `.memo-fingerprints.json` remembers what each file looked like, so a file
+//! that shows up under a new name with the content of one that disappeared
+//! keeps its ID. Fingerprints hold inodes and modification times, which only
+//! mean something on this machine, so unlike the IDs they aren't committed
+//! to git or backed up.
+
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::OsRng;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use crate::error::MemoError;
+use crate::history::rename_history;
+use crate::store::{content_hash, read_metadata_file, write_metadata_file, MemoStore};
+use crate::utils::paths::{is_memo_file, relative_memo_path, walk_memo_files};
+
+pub(crate) const IDS_FILENAME: &str = ".memo-ids.json";
+pub(crate) const FINGERPRINTS_FILENAME: &str = ".memo-fingerprints.json";
+
+/// `.memo-ids.json`: the filename of each memo by its ID
+pub(crate) type MemoIds = HashMap<String, String>;
+
+/// A memo file as it was last seen, to recognize it after a rename
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Fingerprint {
+    size: u64,
+    /// Modification time in milliseconds since the epoch
+    modified: u64,
+    hash: String,
+    /// Only known on Unix
+    #[serde(default)]
+    inode: Option<u64>,
+}
+
+/// 16 random hex digits
+pub(crate) fn random_id() -> String {
+    let mut bytes = [0u8; 8];
+    OsRng.fill_bytes(&mut bytes);
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn modified_millis(metadata: &fs::Metadata) -> u64 {
+    metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |duration| duration.as_millis() as u64)
+}
+
+#[cfg(unix)]
+fn inode(metadata: &fs::Metadata) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    Some(metadata.ino())
+}
+
+#[cfg(not(unix))]
+fn inode(_metadata: &fs::Metadata) -> Option<u64> {
+    None
+}
+
+impl Fingerprint {
+    fn of_file(path: &Path) -> Option<Fingerprint> {
+        let metadata = fs::metadata(path).ok()?;
+        let contents = fs::read(path).ok()?;
+        Some(Fingerprint {
+            size: metadata.len(),
+            modified: modified_millis(&metadata),
+            hash: content_hash(&contents),
+            inode: inode(&metadata),
+        })
+    }
+
+    /// Whether the file still looks the same, without reading it
+    fn is_current(&self, metadata: &fs::Metadata) -> bool {
+        self.size == metadata.len() && self.modified == modified_millis(metadata)
+    }
+
+    /// Whether `other` is likely this file, renamed and maybe edited since:
+    /// the same inode, and unchanged or modified later rather than an older
+    /// file that got the inode after this one was deleted
+    fn is_same_inode(&self, other: &Fingerprint) -> bool {
+        self.inode.is_some()
+            && self.inode == other.inode
+            && (self.size == other.size || self.modified <= other.modified)
+    }
+}
+
+/// The ID of each memo by its filename
+fn ids_by_filename(ids: &MemoIds) -> HashMap<String, String> {
+    ids.iter().map(|(id, filename)| (filename.clone(), id.clone())).collect()
+}
+
+/// Key metadata stored by memo ID by filename instead. Keys that are
+/// filenames already, as written before memos had IDs, are kept; IDs of
+/// memos that are gone are dropped.
+pub(crate) fn by_filename<V>(stored: HashMap<String, V>, ids: &MemoIds) -> HashMap<String, V> {
+    let (known, legacy): (Vec<_>, Vec<_>) = stored.into_iter().partition(|(key, _)| ids.contains_key(key));
+
+    let mut result: HashMap<String, V> = known.into_iter().map(|(id, value)| (ids[&id].clone(), value)).collect();
+    for (key, value) in legacy {
+        if is_memo_file(Path::new(&key)) {
+            result.entry(key).or_insert(value);
+        }
+    }
+    result
+}
+
+impl MemoStore {
+    fn ids_file(&self) -> PathBuf {
+        self.root().join(IDS_FILENAME)
+    }
+
+    fn fingerprints_file(&self) -> PathBuf {
+        self.root().join(FINGERPRINTS_FILENAME)
+    }
+
+    /// Read `.memo-ids.json` as it is, without looking at the memo files
+    pub(crate) fn read_memo_ids(&self) -> MemoIds {
+        read_metadata_file(&self.ids_file(), "memo IDs").unwrap_or_default()
+    }
+
+    fn write_memo_ids(&self, ids: &MemoIds) -> Result<(), MemoError> {
+        write_metadata_file::<MemoIds, _>(&self.ids_file(), ids, "memo IDs")
+    }
+
+    fn read_fingerprints(&self) -> HashMap<String, Fingerprint> {
+        read_metadata_file(&self.fingerprints_file(), "memo fingerprints").unwrap_or_default()
+    }
+
+    fn write_fingerprints(&self, fingerprints: &HashMap<String, Fingerprint>) -> Result<(), MemoError> {
+        write_metadata_file::<HashMap<String, Fingerprint>, _>(&self.fingerprints_file(), fingerprints, "memo fingerprints")
+    }
+
+    /// Bring `.memo-ids.json` up to date with the memo files on disk and
+    /// return it. New files get an ID, or take over the ID of a memo whose
+    /// file disappeared if they have its inode or else its content, which is
+    /// how renames made outside the app are recognized, also when the file
+    /// was edited on the way; their history moves along. IDs of memos that
+    /// are gone are dropped.
+    pub(crate) fn reconcile_memo_ids(&self) -> Result<MemoIds, MemoError> {
+        let root = self.root();
+        let paths = walk_memo_files(root).map_err(|e| MemoError::io("Failed to read memo directory", root, e))?;
+
+        let mut ids = self.read_memo_ids();
+        let mut fingerprints = self.read_fingerprints();
+        let known = ids_by_filename(&ids);
+        let mut seen = HashSet::new();
+        let mut unknown = Vec::new();
+        let mut ids_changed = false;
+        let mut fingerprints_changed = false;
+
+        for path in paths {
+            let filename = match relative_memo_path(root, &path) {
+                Some(filename) => filename,
+                None => continue,
+            };
+            let id = match known.get(&filename) {
+                Some(id) => id,
+                None => {
+                    unknown.push((path, filename));
+                    continue;
+                }
+            };
+
+            seen.insert(id.clone());
+            let current = match (fingerprints.get(id), fs::metadata(&path)) {
+                (Some(fingerprint), Ok(metadata)) => fingerprint.is_current(&metadata),
+                _ => false,
+            };
+            if !current {
+                if let Some(fingerprint) = Fingerprint::of_file(&path) {
+                    fingerprints.insert(id.clone(), fingerprint);
+                    fingerprints_changed = true;
+                }
+            }
+        }
+
+        let mut missing: Vec<String> = ids.keys().filter(|id| !seen.contains(*id)).cloned().collect();
+        missing.sort();
+
+        let unknown: Vec<(String, Fingerprint)> = unknown
+            .into_iter()
+            .filter_map(|(path, filename)| Fingerprint::of_file(&path).map(|fingerprint| (filename, fingerprint)))
+            .collect();
+
+        // A file keeping the inode of a missing memo was renamed, and maybe
+        // edited too; only then is one with the same content taken for it
+        let mut renamed_from: Vec<Option<String>> = vec![None; unknown.len()];
+        for (index, (_, fingerprint)) in unknown.iter().enumerate() {
+            let found = missing
+                .iter()
+                .position(|id| fingerprints.get(id).map_or(false, |known| known.is_same_inode(fingerprint)));
+            renamed_from[index] = found.map(|position| missing.remove(position));
+        }
+        for (index, (_, fingerprint)) in unknown.iter().enumerate() {
+            if renamed_from[index].is_some() {
+                continue;
+            }
+            let found = missing
+                .iter()
+                .position(|id| fingerprints.get(id).map_or(false, |known| known.hash == fingerprint.hash));
+            renamed_from[index] = found.map(|position| missing.remove(position));
+        }
+
+        for ((filename, fingerprint), renamed_from) in unknown.into_iter().zip(renamed_from) {
+            let id = match renamed_from {
+                Some(id) => {
+                    let _ = rename_history(root, &ids[&id], &filename);
+                    id
+                }
+                None => random_id(),
+            };
+            ids.insert(id.clone(), filename);
+            fingerprints.insert(id, fingerprint);
+            ids_changed = true;
+            fingerprints_changed = true;
+        }
+
+        for id in missing {
+            ids.remove(&id);
+            ids_changed = true;
+        }
+        let count = fingerprints.len();
+        fingerprints.retain(|id, _| ids.contains_key(id));
+        fingerprints_changed |= fingerprints.len() != count;
+
+        if ids_changed {
+            self.write_memo_ids(&ids)?;
+        }
+        if fingerprints_changed {
+            self.write_fingerprints(&fingerprints)?;
+        }
+        Ok(ids)
+    }
+
+    /// The ID of each memo in `.memo-ids.json` by filename, after giving one
+    /// to those of `filenames` that exist but don't have one yet
+    pub(crate) fn memo_ids_for<'a>(
+        &self,
+        filenames: impl IntoIterator<Item = &'a String>,
+    ) -> Result<HashMap<String, String>, MemoError> {
+        let mut ids = self.read_memo_ids();
+        let mut by_filename = ids_by_filename(&ids);
+        let mut fingerprints = self.read_fingerprints();
+        let mut changed = false;
+
+        for filename in filenames {
+            if by_filename.contains_key(filename) {
+                continue;
+            }
+            if let Some(fingerprint) = Fingerprint::of_file(&self.root().join(filename)) {
+                let id = random_id();
+                by_filename.insert(filename.clone(), id.clone());
+                ids.insert(id.clone(), filename.clone());
+                fingerprints.insert(id, fingerprint);
+                changed = true;
+            }
+        }
+
+        if changed {
+            self.write_memo_ids(&ids)?;
+            self.write_fingerprints(&fingerprints)?;
+        }
+        Ok(by_filename)
+    }
+
+    /// The ID of a memo, or an empty string if it hasn't been given one yet.
+    /// Memos get theirs when they are created, listed, pinned or ordered.
+    pub(crate) fn memo_id(&self, filename: &str) -> String {
+        self.read_memo_ids()
+            .into_iter()
+            .find(|(_, known)| known == filename)
+            .map(|(id, _)| id)
+            .unwrap_or_default()
+    }
+
+    /// Give a memo the store just created an ID and return it
+    pub(crate) fn assign_memo_id(&self, filename: &str) -> String {
+        let filename = filename.to_string();
+        self.memo_ids_for([&filename])
+            .ok()
+            .and_then(|mut ids| ids.remove(&filename))
+            .unwrap_or_default()
+    }
+
+    /// Move IDs to the new filenames `rename` gives for memos the store moved,
+    /// replacing the ID of any memo that had one of those names before
+    pub(crate) fn rename_memo_ids(&self, rename: impl Fn(&str) -> Option<String>) -> Result<(), MemoError> {
+        let mut ids = self.read_memo_ids();
+        let renamed: HashMap<String, String> = ids
+            .iter()
+            .filter_map(|(id, filename)| rename(filename).map(|filename| (id.clone(), filename)))
+            .collect();
+        if renamed.is_empty() {
+            return Ok(());
+        }
+
+        let targets: HashSet<&String> = renamed.values().collect();
+        ids.retain(|id, filename| renamed.contains_key(id) || !targets.contains(filename));
+        ids.extend(renamed);
+        self.write_memo_ids(&ids)
+    }
+
+    /// Forget the ID of a memo that was deleted
+    pub(crate) fn remove_memo_id(&self, filename: &str) -> Result<(), MemoError> {
+        let mut ids = self.read_memo_ids();
+        let count = ids.len();
+        ids.retain(|_, known| known != filename);
+        if ids.len() == count {
+            return Ok(());
+        }
+        self.write_memo_ids(&ids)
+    }
+
+    /// Give memos restored from the trash or an archive back the IDs they
+    /// had, given as (ID, filename), unless the ID or the file is taken by
+    /// another memo already
+    pub(crate) fn restore_memo_ids(&self, restored: &[(String, String)]) -> Result<(), MemoError> {
+        let mut ids = self.read_memo_ids();
+        let mut taken: HashSet<String> = ids.values().cloned().collect();
+        let mut changed = false;
+        for (id, filename) in restored {
+            if ids.contains_key(id) || taken.contains(filename) {
+                continue;
+            }
+            ids.insert(id.clone(), filename.clone());
+            taken.insert(filename.clone());
+            changed = true;
+        }
+        if !changed {
+            return Ok(());
+        }
+        self.write_memo_ids(&ids)
+    }
+
+    /// Look up the current filename of the memo with the given ID, following
+    /// renames made outside the app
+    pub fn find_memo_by_id(&self, id: &str) -> Result<Option<String>, MemoError> {
+        Ok(self.reconcile_memo_ids()?.remove(id))
+    }
+}
@@ -0,0 +1,42 @@
+//! Storage core of the memo app, independent of Tauri.
+//!
+//! A [`MemoStore`] owns one memo directory and implements everything the app
+//! does with it: listing, reading and saving memos, stable memo IDs, pins
+//! and sidebar order, folders, tags, backlinks, the trash, version history,
+//! search, passphrase-encrypted memos, HTML export, zip backups, git and
+//! syncing with a second directory. The Tauri commands, the headless CLI or a
+//! server are thin wrappers around it.
+
+pub mod search;
+pub mod utils;
+
+mod archive;
+mod encryption;
+mod error;
+mod export;
+mod folder;
+mod git;
+mod history;
+mod ids;
+mod links;
+mod merge;
+mod store;
+mod sync;
+mod tags;
+#[cfg(test)]
+mod test_utils;
+mod trash;
+
+pub use archive::{ArchiveManifest, ArchiveRestore, ArchivedFile, ConflictPolicy, RenamedFile};
+pub use error::{IoErrorKind, MemoError};
+pub use export::ExportedSite;
+pub use git::{GitChange, GitCommit, GitConflict, GitPullResult, GitStatus};
+pub use history::{DiffHunk, DiffLine, MemoVersion};
+pub use links::Backlink;
+pub use merge::{merge_texts, MemoMerge, MergeConflict, TextMerge};
+pub use store::{MemoMetadata, MemoPage, MemoStore, MemoSummary, PinToggle, SavedMemo};
+pub use sync::{SyncConflict, SyncReport};
+pub use tags::TagCount;
+pub use trash::TrashEntry;
+pub use utils::front_matter::FrontMatter;
+pub use utils::paths::MemoPath;
@@ -0,0 +1,115 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+
+use crate::error::MemoError;
+use crate::history::snapshot_before_save;
+use crate::search;
+use crate::store::MemoStore;
+use crate::utils::atomic::atomic_write;
+use crate::utils::links::{link_key, rewrite_links};
+use crate::utils::paths::{memo_title, MemoPath};
+
+/// A memo linking to another one through a `[[link]]`
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Backlink {
+    pub filename: String,
+    pub title: String,
+    /// Line of the first link to the memo
+    pub line_number: usize,
+    /// Text of that line, for showing the link in context
+    pub context: String,
+}
+
+impl MemoStore {
+    /// Normalized names other memos can link to `filename` with: its title, its
+    /// path without extension, and any aliases in its front matter
+    fn link_keys(&self, filename: &str) -> Vec<String> {
+        let mut keys = vec![link_key(memo_title(filename)), link_key(filename)];
+
+        if let Ok(memo) = self.load(filename, false) {
+            if let Some(front_matter) = memo.front_matter {
+                keys.extend(front_matter.aliases.iter().map(|alias| link_key(alias)));
+            }
+        }
+
+        keys.dedup();
+        keys
+    }
+
+    /// Find the memos linking to `filename`, by title, path or alias
+    pub fn backlinks(&self, filename: &MemoPath) -> Result<Vec<Backlink>, MemoError> {
+        let memo_dir = self.root();
+        let keys = self.link_keys(filename);
+
+        let mut backlinks = Vec::new();
+        for (source, line_number) in search::backlinks(memo_dir, &keys)? {
+            if source == filename.as_str() {
+                continue;
+            }
+
+            let context = fs::read_to_string(memo_dir.join(&source))
+                .ok()
+                .and_then(|content| content.lines().nth(line_number - 1).map(|line| line.trim().to_string()))
+                .unwrap_or_default();
+
+            backlinks.push(Backlink {
+                title: memo_title(&source).to_string(),
+                filename: source,
+                line_number,
+                context,
+            });
+        }
+
+        Ok(backlinks)
+    }
+
+    /// Point links to a renamed memo at its new name, keeping the form they were
+    /// written in: `[[Old title]]` becomes `[[New title]]` and `[[folder/Old title]]`
+    /// becomes `[[folder/New title]]`. Returns the number of memos changed.
+    pub(crate) fn rewrite_incoming_links(&self, old_filename: &str, new_filename: &str) -> Result<usize, MemoError> {
+        let memo_dir = self.root();
+        let old_title = link_key(memo_title(old_filename));
+        let old_path = link_key(old_filename);
+        let new_title = memo_title(new_filename).to_string();
+        let new_path = new_filename.trim_end_matches(".md").trim_end_matches(".txt").to_string();
+
+        let mut changed = 0;
+        for (source, _) in search::backlinks(memo_dir, &[old_title.clone(), old_path.clone()])? {
+            // The memo being saved holds its own text in the editor; rewriting it
+            // here would be undone by the next save
+            if source == new_filename {
+                continue;
+            }
+
+            let path = memo_dir.join(&source);
+            let content = match fs::read_to_string(&path) {
+                Ok(content) => content,
+                Err(_) => continue,
+            };
+
+            let rewritten = rewrite_links(&content, |target| {
+                let key = link_key(target);
+                if key == old_path && key.contains('/') {
+                    Some(new_path.clone())
+                } else if key == old_title {
+                    Some(new_title.clone())
+                } else {
+                    None
+                }
+            });
+
+            if let Some(rewritten) = rewritten {
+                let _ = snapshot_before_save(memo_dir, &source, &content, &rewritten);
+                // Not marked as an own write: the UI has to hear about these changes
+                self.mark_change();
+                atomic_write(&path, &rewritten)
+                    .map_err(|e| MemoError::io(&format!("Failed to update links in '{}'", source), &path, e))?;
+                search::index_memo(memo_dir, &source, &rewritten);
+                changed += 1;
+            }
+        }
+
+        Ok(changed)
+    }
+}
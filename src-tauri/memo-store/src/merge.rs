@@ -0,0 +1,296 @@
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use similar::{capture_diff_slices, Algorithm, DiffTag};
+use std::collections::VecDeque;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::error::MemoError;
+use crate::history::find_snapshot;
+use crate::store::{content_hash, split_memo_content, MemoStore};
+use crate::utils::paths::MemoPath;
+
+/// Versions of memos recently read or written, kept so a later merge can
+/// start from the text the editor loaded
+const REMEMBERED_VERSIONS: usize = 64;
+
+/// Recently seen memo files as (path, version, content), oldest first
+static VERSIONS: Lazy<Mutex<VecDeque<(PathBuf, String, String)>>> = Lazy::new(|| Mutex::new(VecDeque::new()));
+
+/// Keep the content of the memo file at `path` that was just read or written,
+/// so it can serve as the base of a merge later. Never called for encrypted memos.
+pub(crate) fn remember_version(path: &Path, content: &str) {
+    let version = content_hash(content.as_bytes());
+    let mut versions = VERSIONS.lock().unwrap_or_else(|e| e.into_inner());
+    versions.retain(|(known_path, known, _)| known_path != path || *known != version);
+    versions.push_back((path.to_path_buf(), version, content.to_string()));
+    if versions.len() > REMEMBERED_VERSIONS {
+        versions.pop_front();
+    }
+}
+
+/// Drop every remembered version of the memo file at `path`
+pub(crate) fn forget_versions(path: &Path) {
+    let mut versions = VERSIONS.lock().unwrap_or_else(|e| e.into_inner());
+    versions.retain(|(known_path, _, _)| known_path != path);
+}
+
+fn remembered_version(path: &Path, version: &str) -> Option<String> {
+    let versions = VERSIONS.lock().unwrap_or_else(|e| e.into_inner());
+    versions
+        .iter()
+        .find(|(known_path, known, _)| known_path == path && known == version)
+        .map(|(_, _, content)| content.clone())
+}
+
+/// Lines both sides changed differently. `line` is where the conflict markers
+/// start in the merged text, counting from 1.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MergeConflict {
+    pub line: usize,
+    pub base: String,
+    pub ours: String,
+    pub theirs: String,
+}
+
+/// Result of a three-way merge. Without conflicts `content` is the merged
+/// text; otherwise it holds both sides of each conflict between
+/// `<<<<<<< ours`, `=======` and `>>>>>>> theirs` markers.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TextMerge {
+    pub content: String,
+    pub conflicts: Vec<MergeConflict>,
+}
+
+impl TextMerge {
+    pub fn is_clean(&self) -> bool {
+        self.conflicts.is_empty()
+    }
+}
+
+/// A memo merge, with the version of the file on disk it was merged with.
+/// Saving the result with that version fails again if the file changed once more.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MemoMerge {
+    pub content: String,
+    pub clean: bool,
+    pub conflicts: Vec<MergeConflict>,
+    pub version: String,
+}
+
+/// A run of base lines one side replaced with other lines
+struct Change {
+    ours: bool,
+    base: Range<usize>,
+    side: Range<usize>,
+}
+
+fn changes(base: &[&str], side: &[&str], ours: bool) -> Vec<Change> {
+    capture_diff_slices(Algorithm::Myers, base, side)
+        .iter()
+        .map(|op| op.as_tag_tuple())
+        .filter(|(tag, _, _)| *tag != DiffTag::Equal)
+        .map(|(_, base, side)| Change { ours, base, side })
+        .collect()
+}
+
+/// Lines of one side standing in for `base_range`, given that side's changes within it
+fn side_lines<'a>(lines: &[&'a str], base_range: &Range<usize>, changes: &[&Change]) -> Vec<&'a str> {
+    let (first, last) = match (changes.first(), changes.last()) {
+        (Some(first), Some(last)) => (first, last),
+        _ => return Vec::new(),
+    };
+    let start = first.side.start - (first.base.start - base_range.start);
+    let end = last.side.end + (base_range.end - last.base.end);
+    lines[start..end].to_vec()
+}
+
+fn push_block(out: &mut String, lines: &[&str]) {
+    for line in lines {
+        out.push_str(line);
+    }
+    if !out.is_empty() && !out.ends_with('\n') {
+        out.push('\n');
+    }
+}
+
+/// Line-based three-way merge of two texts that both started from `base`.
+/// Changes made on only one side are taken over; overlapping or adjacent
+/// changes that differ become conflicts.
+pub fn merge_texts(base: &str, ours: &str, theirs: &str) -> TextMerge {
+    let base_lines: Vec<&str> = base.split_inclusive('\n').collect();
+    let our_lines: Vec<&str> = ours.split_inclusive('\n').collect();
+    let their_lines: Vec<&str> = theirs.split_inclusive('\n').collect();
+
+    let mut all_changes = changes(&base_lines, &our_lines, true);
+    all_changes.extend(changes(&base_lines, &their_lines, false));
+    all_changes.sort_by_key(|change| (change.base.start, change.base.end));
+
+    let mut content = String::new();
+    let mut conflicts = Vec::new();
+    let mut copied_to = 0;
+    let mut index = 0;
+
+    while index < all_changes.len() {
+        // Changes that overlap or touch are resolved together
+        let start = all_changes[index].base.start;
+        let mut end = all_changes[index].base.end;
+        let mut group = vec![&all_changes[index]];
+        index += 1;
+        while index < all_changes.len() && all_changes[index].base.start <= end {
+            end = end.max(all_changes[index].base.end);
+            group.push(&all_changes[index]);
+            index += 1;
+        }
+
+        content.extend(base_lines[copied_to..start].iter().copied());
+        copied_to = end;
+
+        let range = start..end;
+        let (our_changes, their_changes): (Vec<&Change>, Vec<&Change>) = group.iter().partition(|change| change.ours);
+        let our_block = side_lines(&our_lines, &range, &our_changes);
+        let their_block = side_lines(&their_lines, &range, &their_changes);
+
+        if their_changes.is_empty() || our_block == their_block {
+            content.extend(our_block);
+        } else if our_changes.is_empty() {
+            content.extend(their_block);
+        } else {
+            if !content.is_empty() && !content.ends_with('\n') {
+                content.push('\n');
+            }
+            conflicts.push(MergeConflict {
+                line: content.lines().count() + 1,
+                base: base_lines[range].concat(),
+                ours: our_block.concat(),
+                theirs: their_block.concat(),
+            });
+            content.push_str("<<<<<<< ours\n");
+            push_block(&mut content, &our_block);
+            content.push_str("=======\n");
+            push_block(&mut content, &their_block);
+            content.push_str(">>>>>>> theirs\n");
+        }
+    }
+    content.extend(base_lines[copied_to..].iter().copied());
+
+    TextMerge { content, conflicts }
+}
+
+impl MemoStore {
+    /// The content a memo file had at `version`, if it was read or written
+    /// recently or is kept in the memo's history
+    fn content_at_version(&self, filename: &MemoPath, version: &str) -> Option<String> {
+        remembered_version(&self.root().join(filename), version).or_else(|| find_snapshot(self.root(), filename, version))
+    }
+
+    /// Merge `content`, edited from the memo as it was at `base_version`, with
+    /// what is on disk now, e.g. after `save` failed with a conflict. Markdown
+    /// bodies are merged and the front matter on disk is kept.
+    pub fn merge_memo(&self, filename: &MemoPath, base_version: &str, content: &str) -> Result<MemoMerge, MemoError> {
+        let current = self.read(filename, false)?;
+        if current.version == base_version {
+            return Ok(MemoMerge {
+                content: content.to_string(),
+                clean: true,
+                conflicts: Vec::new(),
+                version: current.version,
+            });
+        }
+
+        let base = self.content_at_version(filename, base_version).ok_or_else(|| {
+            MemoError::not_found(
+                format!("The version of '{}' the edit started from is no longer known", filename),
+                &self.root().join(filename),
+            )
+        })?;
+        let base_body = split_memo_content(filename.as_str(), &base).1;
+
+        let merged = merge_texts(base_body, content, &current.content);
+        Ok(MemoMerge {
+            clean: merged.is_clean(),
+            content: merged.content,
+            conflicts: merged.conflicts,
+            version: current.version,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::TempDir;
+    use std::fs;
+
+    #[test]
+    fn test_merge_texts() {
+        let base = "one\ntwo\nthree\nfour\nfive\n";
+
+        let merged = merge_texts(base, "ONE\ntwo\nthree\nfour\nfive\n", "one\ntwo\nthree\nfour\nFIVE\nsix\n");
+        assert!(merged.is_clean());
+        assert_eq!(merged.content, "ONE\ntwo\nthree\nfour\nFIVE\nsix\n");
+
+        // The same change on both sides is no conflict
+        let merged = merge_texts(base, "one\n2\nthree\nfour\nfive\n", "one\n2\nthree\nfour\nfive\n");
+        assert_eq!(merged.content, "one\n2\nthree\nfour\nfive\n");
+
+        let merged = merge_texts(base, "one\nours\nthree\nfour\nfive\n", "one\ntheirs\nthree\nfour\nfive\n");
+        assert_eq!(
+            merged.content,
+            "one\n<<<<<<< ours\nours\n=======\ntheirs\n>>>>>>> theirs\nthree\nfour\nfive\n"
+        );
+        assert_eq!(
+            merged.conflicts,
+            vec![MergeConflict {
+                line: 2,
+                base: "two\n".to_string(),
+                ours: "ours\n".to_string(),
+                theirs: "theirs\n".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_merge_memo_from_the_version_read() {
+        let dir = TempDir::new("merge");
+        let store = dir.store("memos");
+
+        let imported = store.import("Plan.md", "---\ntags: [work]\n---\nintro\nmiddle\nend\n").unwrap();
+        let plan = MemoPath::parse(&imported.filename).unwrap();
+        let read = store.read(&plan, false).unwrap();
+
+        fs::write(store.root().join("Plan.md"), "---\ntags: [work]\n---\nintro\nmiddle\nend, edited elsewhere\n").unwrap();
+        assert!(store.check_version(&plan, &read.version).is_err());
+
+        let merged = store.merge_memo(&plan, &read.version, "intro, edited here\nmiddle\nend\n").unwrap();
+        assert!(merged.clean);
+        assert_eq!(merged.content, "intro, edited here\nmiddle\nend, edited elsewhere\n");
+
+        store.check_version(&plan, &merged.version).unwrap();
+        assert!(store.merge_memo(&plan, "unknown", "text").is_err());
+    }
+
+    #[test]
+    fn test_encrypting_forgets_plain_versions() {
+        let dir = TempDir::new("merge-encrypt");
+        let store = dir.store("memos");
+
+        let imported = store.import("Diary.md", "dear diary").unwrap();
+        let diary = MemoPath::parse(&imported.filename).unwrap();
+        let first = store.read(&diary, false).unwrap().version;
+        let second = store.save("Diary", "dear diary, again", Some(&diary), None, false, None).unwrap().version;
+        assert!(store.content_at_version(&diary, &first).is_some());
+        assert!(store.content_at_version(&diary, &second).is_some());
+
+        store.unlock("correct horse").unwrap();
+        store.encrypt_memo(&diary).unwrap();
+        assert!(store.content_at_version(&diary, &first).is_none());
+        assert!(store.content_at_version(&diary, &second).is_none());
+
+        store.lock();
+    }
+}
@@ -0,0 +1,423 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::ops::Bound;
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+use super::tokenizer::{normalize_with_offsets, tokenize};
+use crate::error::MemoError;
+use crate::utils::atomic::atomic_write;
+use crate::utils::links::{extract_links, link_key};
+use crate::utils::paths::{is_encrypted_memo, memo_title, relative_memo_path, walk_memo_files};
+
+/// Bump whenever tokenization or the stored layout changes so old indexes are rebuilt
+const INDEX_VERSION: u32 = 3;
+
+/// Maximum number of characters of a line shown in a snippet
+const SNIPPET_LENGTH: usize = 160;
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchHit {
+    pub filename: String,
+    pub title: String,
+    pub line_number: usize,
+    pub snippet: Vec<SnippetSegment>,
+    pub score: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SnippetSegment {
+    pub text: String,
+    pub highlighted: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct IndexedDocument {
+    modified: u64,
+    size: u64,
+    terms: Vec<String>,
+    /// Normalized target and line number of every `[[link]]` in the memo
+    links: Vec<(String, usize)>,
+}
+
+/// Inverted index over the memos of one directory
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SearchIndex {
+    version: u32,
+    documents: HashMap<String, IndexedDocument>,
+    /// term -> filename -> line number of every occurrence
+    postings: BTreeMap<String, HashMap<String, Vec<usize>>>,
+    #[serde(skip)]
+    dirty: bool,
+}
+
+impl Default for SearchIndex {
+    fn default() -> Self {
+        SearchIndex {
+            version: INDEX_VERSION,
+            documents: HashMap::new(),
+            postings: BTreeMap::new(),
+            dirty: false,
+        }
+    }
+}
+
+/// Modification time (ms since epoch) and size used to detect changed files
+fn file_stamp(metadata: &fs::Metadata) -> (u64, u64) {
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0);
+    (modified, metadata.len())
+}
+
+impl SearchIndex {
+    /// Load the index from disk, starting over if it is missing, corrupt or outdated
+    pub fn load(index_file: &Path) -> Self {
+        fs::read_to_string(index_file)
+            .ok()
+            .and_then(|content| serde_json::from_str::<SearchIndex>(&content).ok())
+            .filter(|index| index.version == INDEX_VERSION)
+            .unwrap_or_default()
+    }
+
+    /// Persist the index if it changed since it was loaded or last saved
+    pub fn save(&mut self, index_file: &Path) -> Result<(), MemoError> {
+        if !self.dirty {
+            return Ok(());
+        }
+
+        let content = serde_json::to_string(self)
+            .map_err(|e| MemoError::format("Failed to serialize search index", Some(index_file), e))?;
+
+        atomic_write(index_file, content)
+            .map_err(|e| MemoError::io("Failed to write search index", index_file, e))?;
+
+        self.dirty = false;
+        Ok(())
+    }
+
+    /// Index (or re-index) a memo's content
+    pub fn update_document(&mut self, filename: &str, content: &str, metadata: &fs::Metadata) {
+        self.remove_document(filename);
+
+        let mut terms: HashMap<String, Vec<usize>> = HashMap::new();
+        for (index, line) in content.lines().enumerate() {
+            for term in tokenize(line) {
+                terms.entry(term).or_default().push(index + 1);
+            }
+        }
+
+        let (modified, size) = file_stamp(metadata);
+        let mut term_list = Vec::with_capacity(terms.len());
+        for (term, lines) in terms {
+            self.postings
+                .entry(term.clone())
+                .or_default()
+                .insert(filename.to_string(), lines);
+            term_list.push(term);
+        }
+
+        let links = extract_links(content)
+            .into_iter()
+            .map(|link| (link_key(&link.target), link.line_number))
+            .collect();
+
+        self.documents.insert(
+            filename.to_string(),
+            IndexedDocument {
+                modified,
+                size,
+                terms: term_list,
+                links,
+            },
+        );
+        self.dirty = true;
+    }
+
+    /// Drop a memo from the index
+    pub fn remove_document(&mut self, filename: &str) {
+        let document = match self.documents.remove(filename) {
+            Some(document) => document,
+            None => return,
+        };
+
+        for term in document.terms {
+            if let Some(postings) = self.postings.get_mut(&term) {
+                postings.remove(filename);
+                if postings.is_empty() {
+                    self.postings.remove(&term);
+                }
+            }
+        }
+        self.dirty = true;
+    }
+
+    /// Memos linking to any of the normalized names in `keys`, with the line
+    /// of their first such link, sorted by filename
+    pub fn backlinks(&self, keys: &[String]) -> Vec<(String, usize)> {
+        let mut backlinks: Vec<(String, usize)> = self
+            .documents
+            .iter()
+            .filter_map(|(filename, document)| {
+                document
+                    .links
+                    .iter()
+                    .find(|(target, _)| keys.contains(target))
+                    .map(|(_, line)| (filename.clone(), *line))
+            })
+            .collect();
+
+        backlinks.sort();
+        backlinks
+    }
+
+    /// Bring the index in line with the files currently in the memo directory,
+    /// re-reading only files whose size or modification time changed
+    pub fn refresh(&mut self, memo_dir: &Path) -> Result<(), MemoError> {
+        let paths = walk_memo_files(memo_dir)
+            .map_err(|e| MemoError::io("Failed to read memo directory", memo_dir, e))?;
+
+        let mut seen = HashSet::new();
+        for path in paths {
+            let filename = match relative_memo_path(memo_dir, &path) {
+                Some(filename) => filename,
+                None => continue,
+            };
+            // Encrypted memos must not end up readable in the index
+            if is_encrypted_memo(&filename) {
+                continue;
+            }
+            let metadata = match fs::metadata(&path) {
+                Ok(metadata) => metadata,
+                Err(_) => continue,
+            };
+
+            let up_to_date = self
+                .documents
+                .get(&filename)
+                .map(|doc| (doc.modified, doc.size) == file_stamp(&metadata))
+                .unwrap_or(false);
+            if !up_to_date {
+                // Files that are not valid UTF-8 are not memos we can search
+                if let Ok(content) = fs::read_to_string(&path) {
+                    self.update_document(&filename, &content, &metadata);
+                }
+            }
+            seen.insert(filename);
+        }
+
+        let removed: Vec<String> = self
+            .documents
+            .keys()
+            .filter(|filename| !seen.contains(*filename))
+            .cloned()
+            .collect();
+        for filename in removed {
+            self.remove_document(&filename);
+        }
+
+        Ok(())
+    }
+
+    /// Find memos containing every term of the query, best matches first
+    pub fn search(&self, memo_dir: &Path, query: &str, limit: usize) -> Vec<SearchHit> {
+        let mut terms = tokenize(query);
+        terms.sort();
+        terms.dedup();
+        if terms.is_empty() {
+            return Vec::new();
+        }
+
+        let total_documents = self.documents.len() as f64;
+        let mut scores: HashMap<&str, f64> = HashMap::new();
+        let mut matched_terms: HashMap<&str, usize> = HashMap::new();
+        // filename -> line number -> number of distinct query terms on that line
+        let mut line_matches: HashMap<&str, BTreeMap<usize, usize>> = HashMap::new();
+
+        for term in &terms {
+            // Every query term also matches longer indexed terms it is a prefix
+            // of, so partial words are found; exact matches weigh more
+            let mut term_postings: HashMap<&str, (f64, Vec<usize>)> = HashMap::new();
+            let candidates = self
+                .postings
+                .range::<str, _>((Bound::Included(term.as_str()), Bound::Unbounded))
+                .take_while(|(indexed, _)| indexed.starts_with(term.as_str()));
+            for (indexed, postings) in candidates {
+                let weight = if indexed == term { 1.0 } else { 0.5 };
+                for (filename, lines) in postings {
+                    let entry = term_postings.entry(filename.as_str()).or_default();
+                    entry.0 += lines.len() as f64 * weight;
+                    entry.1.extend(lines);
+                }
+            }
+            if term_postings.is_empty() {
+                return Vec::new();
+            }
+
+            let idf = (1.0 + total_documents / term_postings.len() as f64).ln();
+            for (filename, (frequency, mut lines)) in term_postings {
+                *scores.entry(filename).or_insert(0.0) += (1.0 + frequency).ln() * idf;
+                *matched_terms.entry(filename).or_insert(0) += 1;
+
+                lines.sort_unstable();
+                lines.dedup();
+                let per_line = line_matches.entry(filename).or_default();
+                for line in lines {
+                    *per_line.entry(line).or_insert(0) += 1;
+                }
+            }
+        }
+
+        let mut hits: Vec<(&str, f64, usize)> = scores
+            .into_iter()
+            .filter(|(filename, _)| matched_terms.get(filename) == Some(&terms.len()))
+            .map(|(filename, score)| {
+                // Prefer the line containing the most query terms, then the earliest one
+                let line_number = line_matches
+                    .get(filename)
+                    .and_then(|lines| {
+                        lines
+                            .iter()
+                            .max_by(|a, b| a.1.cmp(b.1).then(b.0.cmp(a.0)))
+                            .map(|(line, _)| *line)
+                    })
+                    .unwrap_or(1);
+
+                let title_terms = tokenize(memo_title(filename));
+                let title_bonus = terms
+                    .iter()
+                    .filter(|t| title_terms.iter().any(|title_term| title_term.starts_with(t.as_str())))
+                    .count() as f64;
+
+                (filename, score + title_bonus, line_number)
+            })
+            .collect();
+
+        hits.sort_by(|a, b| {
+            b.1.partial_cmp(&a.1)
+                .unwrap_or(std::cmp::Ordering::Equal)
+                .then_with(|| a.0.cmp(b.0))
+        });
+        hits.truncate(limit);
+
+        hits.into_iter()
+            .map(|(filename, score, line_number)| {
+                let line = fs::read_to_string(memo_dir.join(filename))
+                    .ok()
+                    .and_then(|content| content.lines().nth(line_number - 1).map(|l| l.to_string()))
+                    .unwrap_or_default();
+
+                SearchHit {
+                    filename: filename.to_string(),
+                    title: memo_title(filename).to_string(),
+                    line_number,
+                    snippet: build_snippet(&line, &terms),
+                    score,
+                }
+            })
+            .collect()
+    }
+}
+
+/// Cut a line down to a snippet around the first match and split it into
+/// highlighted and plain segments
+fn build_snippet(line: &str, terms: &[String]) -> Vec<SnippetSegment> {
+    let chars: Vec<char> = line.chars().collect();
+    let normalized = normalize_with_offsets(line);
+    let normalized_chars: Vec<char> = normalized.iter().map(|(c, _)| *c).collect();
+
+    let mut marked = vec![false; chars.len()];
+    for term in terms {
+        let term: Vec<char> = term.chars().collect();
+        if term.is_empty() || term.len() > normalized_chars.len() {
+            continue;
+        }
+        for start in 0..=normalized_chars.len() - term.len() {
+            if normalized_chars[start..start + term.len()] == term[..] {
+                // Cover every original char up to where the next normalized char
+                // starts, so e.g. both halves of a half-width "ｶﾞ" get highlighted
+                let from = normalized[start].1;
+                let to = normalized
+                    .get(start + term.len())
+                    .map(|(_, original)| *original)
+                    .unwrap_or(chars.len());
+                for flag in &mut marked[from..to.max(from + 1)] {
+                    *flag = true;
+                }
+            }
+        }
+    }
+
+    let (start, end) = if chars.len() <= SNIPPET_LENGTH {
+        (0, chars.len())
+    } else {
+        let first_match = marked.iter().position(|m| *m).unwrap_or(0);
+        let start = first_match
+            .saturating_sub(SNIPPET_LENGTH / 4)
+            .min(chars.len() - SNIPPET_LENGTH);
+        (start, start + SNIPPET_LENGTH)
+    };
+
+    let mut segments: Vec<SnippetSegment> = Vec::new();
+    for index in start..end {
+        match segments.last_mut() {
+            Some(segment) if segment.highlighted == marked[index] => segment.text.push(chars[index]),
+            _ => segments.push(SnippetSegment {
+                text: chars[index].to_string(),
+                highlighted: marked[index],
+            }),
+        }
+    }
+
+    let ellipsis = || SnippetSegment {
+        text: "…".to_string(),
+        highlighted: false,
+    };
+    if start > 0 {
+        segments.insert(0, ellipsis());
+    }
+    if end < chars.len() {
+        segments.push(ellipsis());
+    }
+
+    segments
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn segment(text: &str, highlighted: bool) -> SnippetSegment {
+        SnippetSegment {
+            text: text.to_string(),
+            highlighted,
+        }
+    }
+
+    #[test]
+    fn test_build_snippet_highlights_matches() {
+        let snippet = build_snippet("Buy Milk and milk tea", &["milk".to_string()]);
+        assert_eq!(
+            snippet,
+            vec![
+                segment("Buy ", false),
+                segment("Milk", true),
+                segment(" and ", false),
+                segment("milk", true),
+                segment(" tea", false),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_build_snippet_highlights_half_width_kana() {
+        let snippet = build_snippet("ｶﾞｲﾄﾞを読む", &["がいど".to_string()]);
+        assert_eq!(snippet, vec![segment("ｶﾞｲﾄﾞ", true), segment("を読む", false)]);
+    }
+}
@@ -0,0 +1,77 @@
+pub mod index;
+pub mod tokenizer;
+
+use once_cell::sync::Lazy;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::error::MemoError;
+use crate::utils::paths::is_encrypted_memo;
+use index::{SearchHit, SearchIndex};
+
+/// Name of the persisted index inside the memo directory
+const INDEX_FILENAME: &str = ".search-index.json";
+
+/// Index of the memo directory currently in use, kept in memory between commands
+static INDEX: Lazy<Mutex<Option<(PathBuf, SearchIndex)>>> = Lazy::new(|| Mutex::new(None));
+
+/// Run `f` against the index of `memo_dir`, loading it from disk on first use
+/// or when the memo directory changed
+fn with_index<T>(memo_dir: &Path, f: impl FnOnce(&mut SearchIndex) -> T) -> T {
+    let mut guard = INDEX.lock().unwrap_or_else(|e| e.into_inner());
+
+    let loaded = matches!(guard.as_ref(), Some((dir, _)) if dir == memo_dir);
+    if !loaded {
+        let index = SearchIndex::load(&memo_dir.join(INDEX_FILENAME));
+        *guard = Some((memo_dir.to_path_buf(), index));
+    }
+
+    let (_, index) = guard.as_mut().expect("search index was just loaded");
+    f(index)
+}
+
+/// Search the memos in `memo_dir`, picking up files changed outside the app first
+pub fn search(memo_dir: &Path, query: &str, limit: usize) -> Result<Vec<SearchHit>, MemoError> {
+    with_index(memo_dir, |index| {
+        index.refresh(memo_dir)?;
+        let hits = index.search(memo_dir, query, limit);
+
+        // The index can always be rebuilt from the memos, so failing to
+        // persist it must not fail the search itself
+        let _ = index.save(&memo_dir.join(INDEX_FILENAME));
+
+        Ok(hits)
+    })
+}
+
+/// Find the memos linking to any of the normalized names in `keys`, picking
+/// up files changed outside the app first
+pub fn backlinks(memo_dir: &Path, keys: &[String]) -> Result<Vec<(String, usize)>, MemoError> {
+    with_index(memo_dir, |index| {
+        index.refresh(memo_dir)?;
+        let backlinks = index.backlinks(keys);
+        let _ = index.save(&memo_dir.join(INDEX_FILENAME));
+        Ok(backlinks)
+    })
+}
+
+/// Update the index after a memo was written by the app
+pub fn index_memo(memo_dir: &Path, filename: &str, content: &str) {
+    if is_encrypted_memo(filename) {
+        return;
+    }
+    if let Ok(metadata) = fs::metadata(memo_dir.join(filename)) {
+        with_index(memo_dir, |index| index.update_document(filename, content, &metadata));
+    }
+}
+
+/// Update the index after a memo was removed by the app. The index on disk
+/// is saved right away, so it doesn't keep the words of a memo that was just
+/// encrypted until the next search.
+pub fn remove_memo(memo_dir: &Path, filename: &str) {
+    with_index(memo_dir, |index| {
+        index.remove_document(filename);
+        let _ = index.save(&memo_dir.join(INDEX_FILENAME));
+    });
+}
@@ -0,0 +1,130 @@
+use unicode_normalization::char::compose;
+use unicode_normalization::UnicodeNormalization;
+
+/// Combining voiced / semi-voiced sound marks produced by NFKC from half-width ﾞ and ﾟ
+fn is_combining_sound_mark(c: char) -> bool {
+    matches!(c, '\u{3099}' | '\u{309A}')
+}
+
+/// Fold katakana onto hiragana so "メモ" and "めも" index the same way
+fn katakana_to_hiragana(c: char) -> char {
+    match c {
+        '\u{30A1}'..='\u{30F6}' => char::from_u32(c as u32 - 0x60).unwrap_or(c),
+        _ => c,
+    }
+}
+
+/// Characters that are indexed as n-grams rather than whitespace-separated words
+fn is_cjk(c: char) -> bool {
+    matches!(c,
+        '\u{3005}' | '\u{3006}' // 々 〆
+        | '\u{3041}'..='\u{309F}' // Hiragana
+        | '\u{30A0}'..='\u{30FF}' // Katakana (incl. ー)
+        | '\u{3400}'..='\u{4DBF}' // CJK Extension A
+        | '\u{4E00}'..='\u{9FFF}' // CJK Unified Ideographs
+        | '\u{F900}'..='\u{FAFF}' // CJK Compatibility Ideographs
+        | '\u{AC00}'..='\u{D7AF}' // Hangul syllables
+    )
+}
+
+/// Normalize text for indexing, keeping the index of the original char each
+/// normalized char starts at so matches can be mapped back for highlighting.
+///
+/// Applies NFKC (full-width ASCII and half-width katakana become their
+/// standard forms), lowercases and folds katakana onto hiragana.
+pub fn normalize_with_offsets(text: &str) -> Vec<(char, usize)> {
+    let mut normalized: Vec<(char, usize)> = Vec::with_capacity(text.len());
+    for (index, c) in text.chars().enumerate() {
+        for n in std::iter::once(c).nfkc() {
+            // Half-width "ｶﾞ" is two chars; merge the mark into the preceding kana
+            if is_combining_sound_mark(n) {
+                if let Some(last) = normalized.last_mut() {
+                    if let Some(composed) = compose(last.0, n) {
+                        last.0 = katakana_to_hiragana(composed);
+                        continue;
+                    }
+                }
+            }
+            for lower in n.to_lowercase() {
+                normalized.push((katakana_to_hiragana(lower), index));
+            }
+        }
+    }
+    normalized
+}
+
+/// Normalize text for indexing
+pub fn normalize(text: &str) -> String {
+    normalize_with_offsets(text).into_iter().map(|(c, _)| c).collect()
+}
+
+/// Split text into normalized search terms.
+///
+/// Runs of Latin letters and digits become one term each. Runs of Japanese
+/// (and other CJK) characters become overlapping bigrams plus the final
+/// character, so every position of the run starts some term and partial
+/// words can be found with a prefix lookup.
+pub fn tokenize(text: &str) -> Vec<String> {
+    let mut terms = Vec::new();
+    let mut word = String::new();
+    let mut cjk_run: Vec<char> = Vec::new();
+
+    let flush_cjk = |run: &mut Vec<char>, terms: &mut Vec<String>| {
+        for pair in run.windows(2) {
+            terms.push(pair.iter().collect());
+        }
+        if let Some(last) = run.last() {
+            terms.push(last.to_string());
+        }
+        run.clear();
+    };
+
+    for c in normalize(text).chars() {
+        if is_cjk(c) {
+            if !word.is_empty() {
+                terms.push(std::mem::take(&mut word));
+            }
+            cjk_run.push(c);
+        } else if c.is_alphanumeric() {
+            flush_cjk(&mut cjk_run, &mut terms);
+            word.push(c);
+        } else {
+            flush_cjk(&mut cjk_run, &mut terms);
+            if !word.is_empty() {
+                terms.push(std::mem::take(&mut word));
+            }
+        }
+    }
+    flush_cjk(&mut cjk_run, &mut terms);
+    if !word.is_empty() {
+        terms.push(word);
+    }
+
+    terms
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tokenize() {
+        assert_eq!(tokenize("Hello, World!"), vec!["hello", "world"]);
+        assert_eq!(tokenize("  foo_bar-baz "), vec!["foo", "bar", "baz"]);
+        assert!(tokenize("--- ***").is_empty());
+    }
+
+    #[test]
+    fn test_tokenize_japanese() {
+        assert_eq!(tokenize("メモ帳"), vec!["めも", "も帳", "帳"]);
+        assert_eq!(tokenize("今日のTODO"), vec!["今日", "日の", "の", "todo"]);
+    }
+
+    #[test]
+    fn test_normalize_width_and_kana() {
+        assert_eq!(normalize("ﾒﾓ"), normalize("メモ"));
+        assert_eq!(normalize("めも"), normalize("メモ"));
+        assert_eq!(normalize("ｶﾞｲﾄﾞ"), "がいど");
+        assert_eq!(normalize("ＡＢＣ１２３"), "abc123");
+    }
+}
@@ -0,0 +1,1361 @@
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use crate::encryption::SecretMemo;
+use crate::error::MemoError;
+use crate::history::{rename_history, snapshot_before_save};
+use crate::ids::by_filename;
+use crate::merge::remember_version;
+use crate::search::{self, index::SearchHit};
+use crate::utils::atomic::{atomic_write, backup_path};
+use crate::utils::front_matter::{normalize_timestamp, parse_front_matter, render_front_matter, FrontMatter};
+use crate::utils::paths::{
+    check_memo_extension, ensure_dir_exists, is_encrypted_memo, join_memo_path, memo_folder, memo_title, normalize_folder,
+    relative_memo_path, sanitize_filename, walk_memo_files, MemoPath,
+};
+use crate::utils::tags::extract_tags;
+
+/// Number of characters of content included in a memo summary
+const PREVIEW_LENGTH: usize = 120;
+
+/// Front matter longer than this is not looked for when only the head of a memo is read
+const MAX_FRONT_MATTER_BYTES: u64 = 64 * 1024;
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MemoMetadata {
+    /// Stays the same when the memo is renamed or moved; empty for a memo
+    /// added outside the app until the memos are listed again
+    pub id: String,
+    /// Path relative to the memo directory, e.g. `work/plan.md`
+    pub filename: String,
+    pub folder: String,
+    pub title: String,
+    pub content: String,
+    pub created_at: String,
+    pub updated_at: String,
+    pub pinned: bool,
+    pub pinned_at: Option<String>,
+    pub front_matter: Option<FrontMatter>,
+    pub tags: Vec<String>,
+    /// Stored as an encrypted `.enc` file
+    pub encrypted: bool,
+    /// Encrypted and the vault is locked: title and content are empty
+    pub locked: bool,
+    /// Hash of the file on disk; pass it back when saving to detect changes made in between
+    pub version: String,
+}
+
+/// What `MemoStore::save` wrote
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SavedMemo {
+    /// The filename actually used, which follows the title
+    pub filename: String,
+    /// Version of the file as written; pass it as `expected_version` to the next save
+    pub version: String,
+}
+
+/// What `MemoStore::toggle_pin` changed
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PinToggle {
+    pub pinned: bool,
+    /// Version of the memo file afterwards, which changes if it has front matter
+    pub version: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MemoSummary {
+    pub id: String,
+    pub filename: String,
+    pub folder: String,
+    pub title: String,
+    pub created_at: String,
+    pub updated_at: String,
+    pub pinned: bool,
+    pub pinned_at: Option<String>,
+    pub size: u64,
+    pub preview: String,
+    pub front_matter: Option<FrontMatter>,
+    pub encrypted: bool,
+    pub locked: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MemoPage {
+    pub items: Vec<MemoSummary>,
+    pub total: usize,
+    pub offset: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct PinData {
+    pub(crate) pinned: bool,
+    pub(crate) pinned_at: Option<String>,
+}
+
+/// Days a deleted memo stays in the trash unless configured otherwise
+const DEFAULT_TRASH_RETENTION_DAYS: u32 = 30;
+
+/// The memos in one directory, together with the metadata files kept next to
+/// them: `.memo-ids.json`, `.pins.json`, `.order.json`, `.trash`, `.history`
+/// and the search index
+#[derive(Debug, Clone)]
+pub struct MemoStore {
+    root: PathBuf,
+    trash_retention_days: u32,
+    write_hook: Option<fn(&Path)>,
+    change_hook: Option<fn(&Path)>,
+}
+
+impl MemoStore {
+    /// Open the memo directory at `root`, creating it if it doesn't exist
+    pub fn open(root: impl Into<PathBuf>) -> Result<MemoStore, MemoError> {
+        let root = root.into();
+        ensure_dir_exists(&root)?;
+        Ok(MemoStore {
+            root,
+            trash_retention_days: DEFAULT_TRASH_RETENTION_DAYS,
+            write_hook: None,
+            change_hook: None,
+        })
+    }
+
+    /// Days a deleted memo stays in the trash; 0 keeps it until purged by hand
+    pub fn with_trash_retention_days(mut self, days: u32) -> MemoStore {
+        self.trash_retention_days = days;
+        self
+    }
+
+    /// Call `hook` with every memo path the store is about to write, rename
+    /// or delete, e.g. so a file watcher can ignore the store's own changes
+    pub fn with_write_hook(mut self, hook: fn(&Path)) -> MemoStore {
+        self.write_hook = Some(hook);
+        self
+    }
+
+    /// Call `hook` with the memo directory after every change the store makes
+    /// to it, including ones other programs should hear about, e.g. to commit
+    /// them to git
+    pub fn with_change_hook(mut self, hook: fn(&Path)) -> MemoStore {
+        self.change_hook = Some(hook);
+        self
+    }
+
+    /// The memo directory
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub(crate) fn trash_retention_days(&self) -> u32 {
+        self.trash_retention_days
+    }
+
+    /// Tell the write and change hooks about a change the store is about to make
+    pub(crate) fn mark_write(&self, path: &Path) {
+        if let Some(hook) = self.write_hook {
+            hook(path);
+        }
+        self.mark_change();
+    }
+
+    /// Tell only the change hook about a change, for writes the file watcher
+    /// should report like any other
+    pub(crate) fn mark_change(&self) {
+        if let Some(hook) = self.change_hook {
+            hook(&self.root);
+        }
+    }
+
+    /// Get the pins file path
+    fn pins_file(&self) -> PathBuf {
+        self.root.join(".pins.json")
+    }
+
+    /// Get the order file path
+    fn order_file(&self) -> PathBuf {
+        self.root.join(".order.json")
+    }
+
+    /// Read pin data by filename
+    pub(crate) fn read_pin_data(&self) -> Result<HashMap<String, PinData>, MemoError> {
+        let pins = read_metadata_file(&self.pins_file(), "pins file")?;
+        Ok(by_filename(pins, &self.read_memo_ids()))
+    }
+
+    /// Write pin data given by filename; the file stores it by memo ID
+    pub(crate) fn write_pin_data(&self, pins: &HashMap<String, PinData>) -> Result<(), MemoError> {
+        let ids = self.memo_ids_for(pins.keys())?;
+        let pins: HashMap<&String, &PinData> = pins
+            .iter()
+            .filter_map(|(filename, pin)| ids.get(filename).map(|id| (id, pin)))
+            .collect();
+        write_metadata_file::<HashMap<String, PinData>, _>(&self.pins_file(), &pins, "pins")
+    }
+
+    /// Read order data by filename
+    pub(crate) fn read_order_data(&self) -> Result<HashMap<String, usize>, MemoError> {
+        let order = read_metadata_file(&self.order_file(), "order file")?;
+        Ok(by_filename(order, &self.read_memo_ids()))
+    }
+
+    /// Write order data given by filename; the file stores it by memo ID
+    pub(crate) fn write_order_data(&self, order: &HashMap<String, usize>) -> Result<(), MemoError> {
+        let ids = self.memo_ids_for(order.keys())?;
+        let order: HashMap<&String, usize> = order
+            .iter()
+            .filter_map(|(filename, position)| ids.get(filename).map(|id| (id, *position)))
+            .collect();
+        write_metadata_file::<HashMap<String, usize>, _>(&self.order_file(), &order, "order")
+    }
+}
+
+/// Read a JSON metadata file such as `.pins.json`. A missing file counts as
+/// empty, and a corrupt one is replaced by its last good copy if there is one.
+pub(crate) fn read_metadata_file<T: DeserializeOwned + Default>(path: &Path, what: &str) -> Result<T, MemoError> {
+    if !path.exists() {
+        return Ok(T::default());
+    }
+
+    let content = fs::read_to_string(path)
+        .map_err(|e| MemoError::io(&format!("Failed to read {}", what), path, e))?;
+
+    Ok(serde_json::from_str(&content)
+        .or_else(|_| read_last_good_copy(path))
+        .unwrap_or_default())
+}
+
+/// Write a JSON metadata file, keeping the current one as its last good copy
+/// if it still parses as `T`
+pub(crate) fn write_metadata_file<T: DeserializeOwned, V: Serialize>(
+    path: &Path,
+    value: &V,
+    what: &str,
+) -> Result<(), MemoError> {
+    let content = serde_json::to_string_pretty(value)
+        .map_err(|e| MemoError::format(&format!("Failed to serialize {}", what), None, e))?;
+
+    keep_last_good_copy::<T>(path);
+    atomic_write(path, content)
+        .map_err(|e| MemoError::io(&format!("Failed to write {} file", what), path, e))?;
+
+    Ok(())
+}
+
+/// Copy a metadata file to its backup before it is replaced, as long as it
+/// still parses, so there is always a last good copy to recover from
+fn keep_last_good_copy<T: DeserializeOwned>(path: &Path) {
+    if let Ok(content) = fs::read_to_string(path) {
+        if serde_json::from_str::<T>(&content).is_ok() {
+            let _ = atomic_write(&backup_path(path), content);
+        }
+    }
+}
+
+/// Read the backup of a metadata file whose main copy is corrupt
+fn read_last_good_copy<T: DeserializeOwned>(path: &Path) -> Result<T, MemoError> {
+    let backup = backup_path(path);
+    let content = fs::read_to_string(&backup)
+        .map_err(|e| MemoError::io("Failed to read backup file", &backup, e))?;
+
+    serde_json::from_str(&content).map_err(|e| MemoError::format("Failed to parse backup file", Some(&backup), e))
+}
+
+/// Hex SHA-256 of a file's bytes, used as the version of a memo and to
+/// compare files when syncing
+pub(crate) fn content_hash(contents: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(contents))
+}
+
+pub(crate) fn file_version(path: &Path) -> Result<String, MemoError> {
+    let contents = fs::read(path).map_err(|e| MemoError::io("Failed to read file", path, e))?;
+    Ok(content_hash(&contents))
+}
+
+/// A memo file found in the memo directory, without its content
+struct MemoEntry {
+    path: PathBuf,
+    id: String,
+    filename: String,
+    title: String,
+    created_at: String,
+    updated_at: String,
+    size: u64,
+    pinned: bool,
+    pinned_at: Option<String>,
+    front_matter: Option<FrontMatter>,
+    encrypted: bool,
+    locked: bool,
+    /// Decrypted content of an encrypted memo, read while listing
+    secret: Option<SecretMemo>,
+}
+
+impl MemoEntry {
+    /// Take title and content of an encrypted memo from its decrypted form,
+    /// or leave them empty while the vault is locked
+    fn with_secret(mut self, secret: Option<SecretMemo>) -> MemoEntry {
+        self.locked = secret.is_none();
+        self.title = secret.as_ref().map(|s| s.title.clone()).unwrap_or_default();
+        self.secret = secret;
+        self
+    }
+
+    /// Attach the memo's content and the version of the file it was read
+    /// from; tags are taken from the front matter and `body`
+    fn into_metadata(self, content: String, body: &str, version: String) -> MemoMetadata {
+        let tags = extract_tags(self.front_matter.as_ref(), body);
+        MemoMetadata {
+            id: self.id,
+            folder: memo_folder(&self.filename).to_string(),
+            filename: self.filename,
+            title: self.title,
+            content,
+            created_at: self.created_at,
+            updated_at: self.updated_at,
+            pinned: self.pinned,
+            pinned_at: self.pinned_at,
+            front_matter: self.front_matter,
+            tags,
+            encrypted: self.encrypted,
+            locked: self.locked,
+            version,
+        }
+    }
+}
+
+/// Separate the front matter of a Markdown memo from its body; .txt memos have none
+pub(crate) fn split_memo_content<'a>(filename: &str, content: &'a str) -> (Option<FrontMatter>, &'a str) {
+    if filename.ends_with(".md") {
+        parse_front_matter(content)
+    } else {
+        (None, content)
+    }
+}
+
+/// Read up to `max_bytes` from the start of a file as text
+fn read_head(path: &Path, max_bytes: u64) -> Result<String, MemoError> {
+    let file = fs::File::open(path)
+        .map_err(|e| MemoError::io("Failed to read file", path, e))?;
+
+    let mut buffer = Vec::new();
+    file.take(max_bytes)
+        .read_to_end(&mut buffer)
+        .map_err(|e| MemoError::io("Failed to read file", path, e))?;
+
+    // The read may have stopped in the middle of a multi-byte char
+    match String::from_utf8(buffer) {
+        Ok(text) => Ok(text),
+        Err(e) => {
+            let valid_up_to = e.utf8_error().valid_up_to();
+            let mut bytes = e.into_bytes();
+            bytes.truncate(valid_up_to);
+            Ok(String::from_utf8(bytes).unwrap_or_default())
+        }
+    }
+}
+
+/// Read only the front matter of a memo, without loading its whole body
+fn read_front_matter(path: &Path, filename: &str) -> Option<FrontMatter> {
+    if !filename.ends_with(".md") {
+        return None;
+    }
+    let head = read_head(path, MAX_FRONT_MATTER_BYTES).ok()?;
+    split_memo_content(filename, &head).0
+}
+
+/// Describe a memo file. Timestamps and pin state written in its front matter
+/// win over filesystem times, which don't survive copying the file elsewhere;
+/// `.pins.json` still decides the pin state of memos it knows about.
+fn build_memo_entry(
+    path: PathBuf,
+    id: String,
+    filename: String,
+    metadata: &fs::Metadata,
+    front_matter: Option<FrontMatter>,
+    pin_data: &HashMap<String, PinData>,
+) -> Result<MemoEntry, MemoError> {
+    let title = memo_title(&filename).to_string();
+
+    let created_at = metadata
+        .created()
+        .or_else(|_| metadata.modified())
+        .map_err(|e| MemoError::io("Failed to get creation time", &path, e))?;
+
+    let updated_at = metadata
+        .modified()
+        .map_err(|e| MemoError::io("Failed to get modification time", &path, e))?;
+
+    let front_matter_time = |value: Option<&String>| value.and_then(|v| normalize_timestamp(v));
+    let created_at = front_matter_time(front_matter.as_ref().and_then(|f| f.created.as_ref()))
+        .unwrap_or_else(|| chrono::DateTime::<chrono::Utc>::from(created_at).to_rfc3339());
+    let updated_at = front_matter_time(front_matter.as_ref().and_then(|f| f.updated.as_ref()))
+        .unwrap_or_else(|| chrono::DateTime::<chrono::Utc>::from(updated_at).to_rfc3339());
+
+    // Get pin status
+    let pin_info = pin_data.get(&filename);
+    let pinned = pin_info
+        .map(|p| p.pinned)
+        .or_else(|| front_matter.as_ref().and_then(|f| f.pinned))
+        .unwrap_or(false);
+    let pinned_at = pin_info.and_then(|p| p.pinned_at.clone());
+
+    Ok(MemoEntry {
+        encrypted: is_encrypted_memo(&filename),
+        path,
+        id,
+        filename,
+        title,
+        created_at,
+        updated_at,
+        size: metadata.len(),
+        pinned,
+        pinned_at,
+        front_matter,
+        locked: false,
+        secret: None,
+    })
+}
+
+/// Number memos by their position within their own folder
+fn folder_order<'a>(filenames: impl Iterator<Item = &'a str>) -> HashMap<String, usize> {
+    let mut next_index: HashMap<&str, usize> = HashMap::new();
+    let mut order = HashMap::new();
+
+    for filename in filenames {
+        let index = next_index.entry(memo_folder(filename)).or_insert(0);
+        order.insert(filename.to_string(), *index);
+        *index += 1;
+    }
+    order
+}
+
+/// Read the beginning of a memo's body for list previews without loading the whole file
+fn read_preview(path: &Path, filename: &str) -> Result<String, MemoError> {
+    // Leave room for a typical front matter block before the body starts
+    let head = read_head(path, (PREVIEW_LENGTH * 4) as u64 + 4096)?;
+    let (_, body) = split_memo_content(filename, &head);
+
+    Ok(preview_text(body))
+}
+
+/// The first characters of a memo body on a single line
+fn preview_text(body: &str) -> String {
+    body.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .chars()
+        .take(PREVIEW_LENGTH)
+        .collect()
+}
+
+/// Put a memo body and its front matter together for writing.
+///
+/// Front matter already at the top of `body` is written as is. Otherwise
+/// `front_matter` replaces the block of the previous version; when it is
+/// omitted the previous block is kept, with `updated` bumped if the body changed.
+pub(crate) fn compose_memo_file(
+    ext: &str,
+    body: &str,
+    front_matter: Option<FrontMatter>,
+    previous: Option<&str>,
+) -> Result<String, MemoError> {
+    if ext != "md" || parse_front_matter(body).0.is_some() {
+        return Ok(body.to_string());
+    }
+
+    let front_matter = match front_matter {
+        Some(front_matter) => front_matter,
+        None => match previous.map(parse_front_matter) {
+            Some((Some(mut front_matter), previous_body)) => {
+                if previous_body != body && !front_matter.is_empty() {
+                    front_matter.updated = Some(
+                        chrono::Local::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, false),
+                    );
+                }
+                front_matter
+            }
+            _ => return Ok(body.to_string()),
+        },
+    };
+
+    render_front_matter(&front_matter, body)
+}
+
+/// Check whether `filename` is what `resolve_unique_filename` could have picked
+/// for `base_name`, i.e. `base_name.ext` or `base_name_N.ext` in any folder
+fn is_filename_for_title(filename: &str, base_name: &str, ext: &str) -> bool {
+    let name = filename.rsplit('/').next().unwrap_or(filename);
+    let stem = match name.strip_suffix(&format!(".{}", ext)) {
+        Some(stem) => stem,
+        None => return false,
+    };
+
+    stem == base_name
+        || stem
+            .strip_prefix(base_name)
+            .and_then(|rest| rest.strip_prefix('_'))
+            .map(|suffix| !suffix.is_empty() && suffix.chars().all(|c| c.is_ascii_digit()))
+            .unwrap_or(false)
+}
+
+/// Check whether two paths point at the same file, e.g. names differing only
+/// in case on a case-insensitive filesystem
+fn is_same_file(a: &Path, b: &Path) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        match (fs::metadata(a), fs::metadata(b)) {
+            (Ok(a), Ok(b)) => a.dev() == b.dev() && a.ino() == b.ino(),
+            _ => false,
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        match (fs::canonicalize(a), fs::canonicalize(b)) {
+            (Ok(a), Ok(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+/// Resolve a unique filename in the memo directory, appending _1, _2, etc. if needed
+pub(crate) fn resolve_unique_filename(memo_dir: &Path, base_name: &str, ext: &str) -> Result<String, MemoError> {
+    let candidate = format!("{}.{}", base_name, ext);
+    if !memo_dir.join(&candidate).exists() {
+        return Ok(candidate);
+    }
+
+    for i in 1..=999 {
+        let suffixed = format!("{}_{}.{}", base_name, i, ext);
+        if !memo_dir.join(&suffixed).exists() {
+            return Ok(suffixed);
+        }
+    }
+
+    Err(MemoError::already_exists(
+        format!("Too many files with name '{}'", base_name),
+        &memo_dir.join(format!("{}.{}", base_name, ext)),
+    ))
+}
+
+/// Free name for keeping the other side of a conflict next to a memo:
+/// `work/Plan.md` -> `work/Plan (conflict 2024-05-01).md`
+pub(crate) fn conflict_copy_filename(memo_dir: &Path, filename: &str, label: &str) -> Result<String, MemoError> {
+    let folder = memo_folder(filename);
+    let name = Path::new(filename);
+    let stem = name.file_stem().and_then(|s| s.to_str()).unwrap_or("memo");
+    let ext = name.extension().and_then(|s| s.to_str()).unwrap_or("md");
+
+    let base_name = format!("{} (conflict {})", stem, label);
+    Ok(join_memo_path(folder, &resolve_unique_filename(&memo_dir.join(folder), &base_name, ext)?))
+}
+
+impl MemoStore {
+    /// Collect every memo in the memo directory, sorted the way the sidebar shows them
+    fn list_entries(&self) -> Result<Vec<MemoEntry>, MemoError> {
+        let paths = walk_memo_files(&self.root)
+            .map_err(|e| MemoError::io("Failed to read memo directory", &self.root, e))?;
+
+        // Catches up with renames made outside the app before pins are read
+        let mut ids: HashMap<String, String> = self
+            .reconcile_memo_ids()
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(id, filename)| (filename, id))
+            .collect();
+        let pin_data = self.read_pin_data().unwrap_or_else(|_| HashMap::new());
+        let mut memos = Vec::new();
+
+        for path in paths {
+            let filename = match relative_memo_path(&self.root, &path) {
+                Some(filename) => filename,
+                None => continue,
+            };
+
+            let metadata = fs::metadata(&path)
+                .map_err(|e| MemoError::io("Failed to read metadata", &path, e))?;
+            let id = ids.remove(&filename).unwrap_or_default();
+
+            let entry = if is_encrypted_memo(&filename) {
+                // Files that don't decrypt show up like locked ones
+                let secret = self.read_secret(&path).unwrap_or(None);
+                let front_matter = secret.as_ref().and_then(|s| s.split().0);
+                build_memo_entry(path, id, filename, &metadata, front_matter, &pin_data)?.with_secret(secret)
+            } else {
+                let front_matter = read_front_matter(&path, &filename);
+                build_memo_entry(path, id, filename, &metadata, front_matter, &pin_data)?
+            };
+
+            memos.push(entry);
+        }
+
+        // Sort with custom order; positions in .order.json count within each folder
+        let order_data = self.read_order_data().unwrap_or_else(|_| HashMap::new());
+
+        memos.sort_by(|a, b| {
+            let a_folder = memo_folder(&a.filename);
+            let b_folder = memo_folder(&b.filename);
+            if a_folder != b_folder {
+                return a_folder.cmp(b_folder);
+            }
+
+            match (a.pinned, b.pinned) {
+                (true, false) => std::cmp::Ordering::Less,
+                (false, true) => std::cmp::Ordering::Greater,
+                (true, true) => {
+                    // Both pinned: sort by custom order, then by pinnedAt
+                    let a_order = order_data.get(&a.filename);
+                    let b_order = order_data.get(&b.filename);
+                    match (a_order, b_order) {
+                        (Some(a_idx), Some(b_idx)) => a_idx.cmp(b_idx),
+                        (Some(_), None) => std::cmp::Ordering::Less,
+                        (None, Some(_)) => std::cmp::Ordering::Greater,
+                        (None, None) => {
+                            match (&a.pinned_at, &b.pinned_at) {
+                                (Some(a_time), Some(b_time)) => a_time.cmp(b_time),
+                                _ => std::cmp::Ordering::Equal,
+                            }
+                        }
+                    }
+                }
+                (false, false) => {
+                    // Both not pinned: sort by custom order, then by updated time
+                    let a_order = order_data.get(&a.filename);
+                    let b_order = order_data.get(&b.filename);
+                    match (a_order, b_order) {
+                        (Some(a_idx), Some(b_idx)) => a_idx.cmp(b_idx),
+                        (Some(_), None) => std::cmp::Ordering::Less,
+                        (None, Some(_)) => std::cmp::Ordering::Greater,
+                        (None, None) => b.updated_at.cmp(&a.updated_at),
+                    }
+                }
+            }
+        });
+
+        // Auto-persist order: if any memo is missing from .order.json, save the
+        // current sorted order so that subsequent loads are stable (no updated_at fallback).
+        let has_unordered = memos.iter().any(|m| !order_data.contains_key(&m.filename));
+        if has_unordered {
+            let _ = self.write_order_data(&folder_order(memos.iter().map(|m| m.filename.as_str())));
+        }
+
+        Ok(memos)
+    }
+
+    /// List every memo with its content, in sidebar order
+    pub fn list(&self) -> Result<Vec<MemoMetadata>, MemoError> {
+        let mut memos = Vec::new();
+        for mut entry in self.list_entries()? {
+            let memo = match entry.secret.take() {
+                Some(secret) => {
+                    let body = secret.split().1;
+                    let version = file_version(&entry.path)?;
+                    entry.into_metadata(body.to_string(), body, version)
+                }
+                None if entry.encrypted => {
+                    let version = file_version(&entry.path)?;
+                    entry.into_metadata(String::new(), "", version)
+                }
+                None => {
+                    let content = fs::read_to_string(&entry.path)
+                        .map_err(|e| MemoError::io("Failed to read file", &entry.path, e))?;
+                    let body = split_memo_content(&entry.filename, &content).1;
+                    entry.into_metadata(body.to_string(), body, content_hash(content.as_bytes()))
+                }
+            };
+
+            memos.push(memo);
+        }
+
+        Ok(memos)
+    }
+
+    /// List memos without their content, one page at a time
+    pub fn list_summaries(&self, offset: usize, limit: Option<usize>) -> Result<MemoPage, MemoError> {
+        let entries = self.list_entries()?;
+        let total = entries.len();
+
+        let mut items = Vec::new();
+        for entry in entries.into_iter().skip(offset).take(limit.unwrap_or(usize::MAX)) {
+            let preview = match &entry.secret {
+                Some(secret) => preview_text(secret.split().1),
+                None if entry.encrypted => String::new(),
+                None => read_preview(&entry.path, &entry.filename)?,
+            };
+
+            items.push(MemoSummary {
+                id: entry.id,
+                folder: memo_folder(&entry.filename).to_string(),
+                filename: entry.filename,
+                title: entry.title,
+                created_at: entry.created_at,
+                updated_at: entry.updated_at,
+                pinned: entry.pinned,
+                pinned_at: entry.pinned_at,
+                size: entry.size,
+                preview,
+                front_matter: entry.front_matter,
+                encrypted: entry.encrypted,
+                locked: entry.locked,
+            });
+        }
+
+        Ok(MemoPage { items, total, offset })
+    }
+
+    /// Search memo contents, best matches first
+    pub fn search(&self, query: &str, limit: usize) -> Result<Vec<SearchHit>, MemoError> {
+        search::search(&self.root, query, limit)
+    }
+
+    /// Read a memo. `content` is the body without front matter unless
+    /// `include_front_matter` asks for the raw file.
+    pub fn read(&self, filename: &MemoPath, include_front_matter: bool) -> Result<MemoMetadata, MemoError> {
+        let file_path = self.root.join(filename);
+
+        if !file_path.exists() {
+            return Err(MemoError::not_found(format!("Memo '{}' not found", filename), &file_path));
+        }
+
+        self.load(filename, include_front_matter)
+    }
+
+    /// Make sure a memo is still at `expected_version`, the `version` it had
+    /// when it was read. If another process changed or deleted it since, fail
+    /// with a conflict carrying what is on disk now.
+    pub fn check_version(&self, filename: &MemoPath, expected_version: &str) -> Result<(), MemoError> {
+        let contents = fs::read(self.root.join(filename)).ok();
+        self.ensure_version(filename, expected_version, contents.as_deref())
+    }
+
+    /// Fail with a conflict unless `contents`, the memo file as just read or
+    /// None if it is gone, is at `expected_version`
+    pub(crate) fn ensure_version(
+        &self,
+        filename: &str,
+        expected_version: &str,
+        contents: Option<&[u8]>,
+    ) -> Result<(), MemoError> {
+        let current_version = contents.map(content_hash);
+        if current_version.as_deref() == Some(expected_version) {
+            return Ok(());
+        }
+
+        let path = self.root.join(filename);
+        let current_content = match current_version {
+            Some(_) => Some(self.load(filename, false)?.content),
+            None => None,
+        };
+        Err(MemoError::conflict(
+            format!("Memo '{}' was changed by another program", filename),
+            &path,
+            current_content,
+            current_version,
+        ))
+    }
+
+    /// Save a memo under a filename derived from its title, in the folder it
+    /// already lives in, and return the filename actually used with the new
+    /// version. If another memo already has that name, a `_N` suffix is added
+    /// instead of overwriting it. The front matter of a Markdown memo is
+    /// preserved unless `front_matter` replaces it; an empty one removes it.
+    /// With `rewrite_links`, `[[links]]` in other memos follow a rename. With
+    /// `expected_version`, the save fails with a conflict if the file it is
+    /// about to replace is at another version.
+    pub fn save(
+        &self,
+        title: &str,
+        content: &str,
+        old_filename: Option<&MemoPath>,
+        front_matter: Option<FrontMatter>,
+        rewrite_links: bool,
+        expected_version: Option<&str>,
+    ) -> Result<SavedMemo, MemoError> {
+        let old_filename = old_filename.map(MemoPath::as_str);
+        if let Some(old) = old_filename.filter(|old| is_encrypted_memo(old)) {
+            return self.save_secret(old, title, content, front_matter, expected_version);
+        }
+
+        let memo_dir = &self.root;
+        let sanitized_title = sanitize_filename(title);
+        let ext = old_filename
+            .and_then(|f| f.rsplit('.').next())
+            .unwrap_or("md")
+            .to_string();
+        let folder = old_filename.map(memo_folder).unwrap_or("").to_string();
+        let candidate = join_memo_path(&folder, &format!("{}.{}", sanitized_title, ext));
+
+        let new_filename = match old_filename {
+            // Already named after this title (possibly with a suffix from an
+            // earlier collision): keep the name instead of renaming on every save
+            Some(old) if is_filename_for_title(old, &sanitized_title, &ext) => old.to_string(),
+            Some(old) if is_same_file(&memo_dir.join(old), &memo_dir.join(&candidate)) => candidate,
+            _ => join_memo_path(
+                &folder,
+                &resolve_unique_filename(&memo_dir.join(&folder), &sanitized_title, &ext)?,
+            ),
+        };
+        let new_path = memo_dir.join(&new_filename);
+        let renamed_from = old_filename.filter(|old| *old != new_filename);
+
+        // Keep what is about to be overwritten in the memo's history. History is a
+        // safety net, so failing to record it must not block the save itself.
+        let previous_path = renamed_from
+            .map(|old| memo_dir.join(old))
+            .unwrap_or_else(|| new_path.clone());
+        let previous = fs::read_to_string(&previous_path).ok();
+        if let (Some(old), Some(expected_version)) = (old_filename, expected_version) {
+            self.ensure_version(old, expected_version, previous.as_deref().map(str::as_bytes))?;
+        }
+        let content = compose_memo_file(&ext, content, front_matter, previous.as_deref())?;
+        if let Some(previous) = &previous {
+            if let Some(old) = renamed_from {
+                let _ = rename_history(memo_dir, old, &new_filename);
+            }
+            let _ = snapshot_before_save(memo_dir, &new_filename, previous, &content);
+        }
+
+        if let Some(old) = renamed_from {
+            let old_path = memo_dir.join(old);
+            // A change only in letter case names the same file on case-insensitive
+            // filesystems; rename it first so removing the old name later can't
+            // delete the freshly written memo
+            if is_same_file(&old_path, &new_path) {
+                self.mark_write(&old_path);
+                self.mark_write(&new_path);
+                fs::rename(&old_path, &new_path)
+                    .map_err(|e| MemoError::io("Failed to rename memo", &old_path, e))?;
+            }
+        }
+
+        // Write the new file
+        self.mark_write(&new_path);
+        atomic_write(&new_path, &content)
+            .map_err(|e| MemoError::io("Failed to write memo", &new_path, e))?;
+        search::index_memo(memo_dir, &new_filename, &content);
+        remember_version(&new_path, &content);
+
+        // If filename changed, delete the old file and carry over pin and order
+        if let Some(old) = renamed_from {
+            let old_path = memo_dir.join(old);
+            if old_path.exists() && !is_same_file(&old_path, &new_path) {
+                self.mark_write(&old_path);
+                fs::remove_file(&old_path)
+                    .map_err(|e| MemoError::io("Failed to delete old file", &old_path, e))?;
+            }
+            search::remove_memo(memo_dir, old);
+            self.carry_pin_and_order(old, &new_filename, true);
+
+            if rewrite_links {
+                self.rewrite_incoming_links(old, &new_filename)?;
+            }
+        }
+
+        Ok(SavedMemo {
+            version: content_hash(content.as_bytes()),
+            filename: new_filename,
+        })
+    }
+
+    /// Move a memo's ID to its new name, which takes its pin along. Its
+    /// position in .order.json moves along when `keep_position` is set and is
+    /// dropped otherwise, which puts the memo at the end of its folder.
+    pub(crate) fn carry_pin_and_order(&self, old_filename: &str, new_filename: &str, keep_position: bool) {
+        let _ = self.rename_memo_ids(|filename| (filename == old_filename).then(|| new_filename.to_string()));
+
+        // Entries written before memos had IDs are still keyed by filename
+        if let Ok(mut pins) = self.read_pin_data() {
+            if let Some(pin) = pins.remove(old_filename) {
+                pins.insert(new_filename.to_string(), pin);
+                let _ = self.write_pin_data(&pins);
+            }
+        }
+
+        if let Ok(mut order) = self.read_order_data() {
+            let mut changed = false;
+            if let Some(position) = order.remove(old_filename) {
+                order.insert(new_filename.to_string(), position);
+                changed = true;
+            }
+            if !keep_position {
+                changed |= order.remove(new_filename).is_some();
+            }
+            if changed {
+                let _ = self.write_order_data(&order);
+            }
+        }
+    }
+
+    /// Move a memo into another folder ("" for the top level), keeping its pin
+    /// and history. Returns the memo's new filename.
+    pub fn move_memo(&self, filename: &MemoPath, folder: &str) -> Result<String, MemoError> {
+        let memo_dir = &self.root;
+        let folder = normalize_folder(folder)?;
+        let old_path = memo_dir.join(filename);
+
+        if !old_path.exists() {
+            return Err(MemoError::not_found(format!("Memo '{}' not found", filename), &old_path));
+        }
+        if memo_folder(filename) == folder {
+            return Ok(filename.to_string());
+        }
+
+        let name = Path::new(filename.as_str());
+        let stem = name.file_stem().and_then(|s| s.to_str()).unwrap_or("memo");
+        let ext = name.extension().and_then(|s| s.to_str()).unwrap_or("md");
+
+        let folder_dir = memo_dir.join(&folder);
+        ensure_dir_exists(&folder_dir)?;
+        let new_filename = join_memo_path(&folder, &resolve_unique_filename(&folder_dir, stem, ext)?);
+        let new_path = memo_dir.join(&new_filename);
+
+        self.mark_write(&old_path);
+        self.mark_write(&new_path);
+        fs::rename(&old_path, &new_path)
+            .map_err(|e| MemoError::io("Failed to move memo", &old_path, e))?;
+
+        let _ = rename_history(memo_dir, filename, &new_filename);
+        search::remove_memo(memo_dir, filename);
+        if let Ok(content) = fs::read_to_string(&new_path) {
+            search::index_memo(memo_dir, &new_filename, &content);
+        }
+        self.carry_pin_and_order(filename, &new_filename, false);
+
+        Ok(new_filename)
+    }
+
+    /// Move a memo to the trash; it can be brought back with `restore`
+    pub fn delete(&self, filename: &MemoPath) -> Result<(), MemoError> {
+        if self.root.join(filename).exists() {
+            self.move_to_trash(filename)?;
+        }
+
+        Ok(())
+    }
+
+    /// Create an empty memo with the given extension, at the top level or inside `folder`
+    pub fn create(&self, ext: &str, folder: &str) -> Result<MemoMetadata, MemoError> {
+        check_memo_extension(ext)?;
+        let now_local = chrono::Local::now();
+        let title = now_local.format("メモ_%Y%m%d_%H%M").to_string();
+        let content = String::new();
+        let sanitized = sanitize_filename(&title);
+        let memo_dir = &self.root;
+        let folder = normalize_folder(folder)?;
+        ensure_dir_exists(&memo_dir.join(&folder))?;
+        // 同名ファイルが存在する場合はサフィックスを付ける
+        let mut filename_with_ext = join_memo_path(&folder, &format!("{}.{}", sanitized, ext));
+        let mut final_title = title;
+        let mut counter = 2;
+        while memo_dir.join(&filename_with_ext).exists() {
+            final_title = format!("{}_{}", now_local.format("メモ_%Y%m%d_%H%M"), counter);
+            let sanitized_new = sanitize_filename(&final_title);
+            filename_with_ext = join_memo_path(&folder, &format!("{}.{}", sanitized_new, ext));
+            counter += 1;
+        }
+        let file_path = memo_dir.join(&filename_with_ext);
+        self.mark_write(&file_path);
+        atomic_write(&file_path, &content)
+            .map_err(|e| MemoError::io("Failed to create memo", &file_path, e))?;
+        search::index_memo(memo_dir, &filename_with_ext, &content);
+
+        let now = chrono::Utc::now().to_rfc3339();
+        let version = content_hash(content.as_bytes());
+
+        Ok(MemoMetadata {
+            id: self.assign_memo_id(&filename_with_ext),
+            filename: filename_with_ext,
+            folder,
+            title: final_title,
+            content,
+            created_at: now.clone(),
+            updated_at: now,
+            pinned: false,
+            pinned_at: None,
+            front_matter: None,
+            tags: Vec::new(),
+            encrypted: false,
+            locked: false,
+            version,
+        })
+    }
+
+    /// Pin or unpin a memo. The `pinned` field of its front matter is kept
+    /// in sync, which changes the file, so the new version comes back too.
+    pub fn toggle_pin(&self, filename: &MemoPath) -> Result<PinToggle, MemoError> {
+        let filename = filename.as_str();
+        let file_path = self.root.join(filename);
+        if !file_path.exists() {
+            return Err(MemoError::not_found(format!("Memo '{}' not found", filename), &file_path));
+        }
+        let mut pins = self.read_pin_data()?;
+        let content = fs::read_to_string(&file_path)
+            .map_err(|e| MemoError::io("Failed to read file", &file_path, e))?;
+        let (front_matter, body) = split_memo_content(filename, &content);
+
+        // Memos not in .pins.json yet may be pinned by their front matter
+        let was_pinned = pins
+            .get(filename)
+            .map(|p| p.pinned)
+            .or_else(|| front_matter.as_ref().and_then(|f| f.pinned))
+            .unwrap_or(false);
+        let is_pinned = !was_pinned;
+
+        pins.insert(
+            filename.to_string(),
+            PinData {
+                pinned: is_pinned,
+                // Set pinned time, or clear it when unpinning
+                pinned_at: is_pinned.then(|| chrono::Utc::now().to_rfc3339()),
+            },
+        );
+        self.write_pin_data(&pins)?;
+
+        // Keep the pin state written in the memo itself in sync
+        let mut version = content_hash(content.as_bytes());
+        if let Some(mut front_matter) = front_matter {
+            front_matter.pinned = Some(is_pinned);
+            let updated = render_front_matter(&front_matter, body)?;
+            let _ = snapshot_before_save(&self.root, filename, &content, &updated);
+            self.mark_write(&file_path);
+            atomic_write(&file_path, &updated)
+                .map_err(|e| MemoError::io("Failed to write memo", &file_path, e))?;
+            search::index_memo(&self.root, filename, &updated);
+            remember_version(&file_path, &updated);
+            version = content_hash(updated.as_bytes());
+        }
+
+        Ok(PinToggle { pinned: is_pinned, version })
+    }
+
+    /// Store the order of memos as shown in the sidebar. Positions count within
+    /// each folder; folders without memos in `filenames` keep their order.
+    pub fn update_order(&self, filenames: &[MemoPath]) -> Result<(), MemoError> {
+        let folders: HashSet<&str> = filenames.iter().map(|f| memo_folder(f)).collect();
+
+        let mut order = self.read_order_data().unwrap_or_default();
+        order.retain(|filename, _| !folders.contains(memo_folder(filename)));
+        order.extend(folder_order(filenames.iter().map(MemoPath::as_str)));
+
+        self.write_order_data(&order)?;
+        Ok(())
+    }
+
+    /// Like `load` without front matter, but an encrypted memo that can't be
+    /// read while the vault is locked comes back empty and marked as locked
+    pub(crate) fn load_or_locked(&self, filename: &str) -> Result<MemoMetadata, MemoError> {
+        let path = self.root.join(filename);
+        if !is_encrypted_memo(filename) || self.is_unlocked() {
+            return self.load(filename, false);
+        }
+
+        let metadata = fs::metadata(&path)
+            .map_err(|e| MemoError::io("Failed to read metadata", &path, e))?;
+        let pin_data = self.read_pin_data().unwrap_or_else(|_| HashMap::new());
+        let version = file_version(&path)?;
+        let entry = build_memo_entry(path, self.memo_id(filename), filename.to_string(), &metadata, None, &pin_data)?;
+        Ok(entry.with_secret(None).into_metadata(String::new(), "", version))
+    }
+
+    /// Read a memo file into MemoMetadata, with or without its front matter in `content`
+    pub(crate) fn load(&self, filename: &str, include_front_matter: bool) -> Result<MemoMetadata, MemoError> {
+        let path = self.root.join(filename);
+        let metadata = fs::metadata(&path)
+            .map_err(|e| MemoError::io("Failed to read metadata", &path, e))?;
+        let id = self.memo_id(filename);
+        let pin_data = self.read_pin_data().unwrap_or_else(|_| HashMap::new());
+
+        if is_encrypted_memo(filename) {
+            let secret = self
+                .read_secret(&path)?
+                .ok_or_else(|| MemoError::locked(format!("Memo '{}' is locked", filename), &path))?;
+            let (front_matter, body) = secret.split();
+            let content = if include_front_matter { secret.content.clone() } else { body.to_string() };
+
+            let version = file_version(&path)?;
+            let mut entry = build_memo_entry(path, id.clone(), filename.to_string(), &metadata, front_matter, &pin_data)?;
+            entry.title = secret.title.clone();
+            return Ok(entry.into_metadata(content, body, version));
+        }
+
+        let raw = fs::read_to_string(&path)
+            .map_err(|e| MemoError::io("Failed to read file", &path, e))?;
+        remember_version(&path, &raw);
+
+        let (front_matter, body) = split_memo_content(filename, &raw);
+        let content = if include_front_matter { raw.clone() } else { body.to_string() };
+
+        let entry = build_memo_entry(path, id, filename.to_string(), &metadata, front_matter, &pin_data)?;
+        Ok(entry.into_metadata(content, body, content_hash(raw.as_bytes())))
+    }
+
+    /// Add a memo with the given content at the top level, named after
+    /// `original_filename` and keeping its extension
+    pub fn import(&self, original_filename: &str, content: &str) -> Result<MemoMetadata, MemoError> {
+        let stem = Path::new(original_filename)
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or("imported_memo")
+            .to_string();
+
+        let ext = Path::new(original_filename)
+            .extension()
+            .and_then(|s| s.to_str())
+            .unwrap_or("md");
+        check_memo_extension(ext)?;
+
+        let sanitized = sanitize_filename(&stem);
+        let target_filename = resolve_unique_filename(&self.root, &sanitized, ext)?;
+        let target_path = self.root.join(&target_filename);
+
+        self.mark_write(&target_path);
+        atomic_write(&target_path, content)
+            .map_err(|e| MemoError::io("Failed to write imported file", &target_path, e))?;
+        search::index_memo(&self.root, &target_filename, content);
+
+        self.load(&target_filename, false)
+    }
+
+    /// Import a single .md or .txt file from an arbitrary path
+    pub fn import_file(&self, source_path: &Path) -> Result<MemoMetadata, MemoError> {
+        let ext = source_path
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or("");
+        check_memo_extension(ext)?;
+
+        let content = fs::read_to_string(source_path)
+            .map_err(|e| MemoError::io("Failed to read file", source_path, e))?;
+
+        let name = source_path
+            .file_name()
+            .and_then(|s| s.to_str())
+            .unwrap_or("imported_memo.md");
+
+        self.import(name, &content)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::TempDir;
+
+    #[test]
+    fn test_is_filename_for_title() {
+        assert!(is_filename_for_title("Plan.md", "Plan", "md"));
+        assert!(is_filename_for_title("Plan_2.md", "Plan", "md"));
+        assert!(is_filename_for_title("work/Plan.md", "Plan", "md"));
+        assert!(!is_filename_for_title("Plan_x.md", "Plan", "md"));
+        assert!(!is_filename_for_title("Plan_.md", "Plan", "md"));
+        assert!(!is_filename_for_title("Plan.txt", "Plan", "md"));
+        assert!(!is_filename_for_title("Planning.md", "Plan", "md"));
+    }
+
+    fn path(filename: &str) -> MemoPath {
+        MemoPath::parse(filename).unwrap()
+    }
+
+    #[test]
+    fn test_save_rename_and_pin() {
+        let dir = TempDir::new("save");
+        let store = dir.store("memos");
+        let created = store.create("md", "work").unwrap();
+
+        let filename = store.save("Plan", "first", Some(&path(&created.filename)), None, false, None).unwrap().filename;
+        assert_eq!(filename, "work/Plan.md");
+        assert!(!store.root().join(&created.filename).exists());
+        assert!(store.toggle_pin(&path(&filename)).unwrap().pinned);
+
+        // Another memo with the same title gets a suffix instead of overwriting
+        let other = store.create("md", "work").unwrap();
+        let other = store.save("Plan", "second", Some(&path(&other.filename)), None, false, None).unwrap().filename;
+        assert_eq!(other, "work/Plan_1.md");
+
+        let memos = store.list().unwrap();
+        assert_eq!(memos[0].filename, "work/Plan.md");
+        assert!(memos[0].pinned);
+        assert_eq!(store.read(&path(&other), false).unwrap().content, "second");
+    }
+
+    #[test]
+    fn test_dot_prefixed_title_stays_visible() {
+        let dir = TempDir::new("dot-title");
+        let store = dir.store("memos");
+        let created = store.create("md", "").unwrap();
+
+        let filename = store.save(".env notes", "SECRET=1", Some(&path(&created.filename)), None, false, None).unwrap().filename;
+        assert_eq!(filename, "env notes.md");
+        let memos = store.list().unwrap();
+        assert_eq!(memos.len(), 1);
+        assert_eq!(memos[0].filename, filename);
+        assert_eq!(store.read(&path(&filename), false).unwrap().content, "SECRET=1");
+    }
+
+    #[test]
+    fn test_create_and_import_reject_other_extensions() {
+        let dir = TempDir::new("extension");
+        let store = dir.store("memos");
+
+        assert!(matches!(store.create("sh", ""), Err(MemoError::InvalidInput { .. })));
+        assert!(matches!(store.create("txt.enc", ""), Err(MemoError::InvalidInput { .. })));
+        assert!(matches!(store.import("run.sh", "echo"), Err(MemoError::InvalidInput { .. })));
+        assert!(walk_memo_files(store.root()).unwrap().is_empty());
+
+        assert_eq!(store.import("notes.txt", "text").unwrap().filename, "notes.txt");
+    }
+
+    #[test]
+    fn test_delete_and_restore() {
+        let dir = TempDir::new("trash");
+        let store = dir.store("memos");
+        let memo = store.import("Notes.txt", "keep me").unwrap();
+        store.create_folder("work").unwrap();
+        let work = store.import("Work.md", "elsewhere").unwrap();
+        let work = store.move_memo(&path(&work.filename), "work").unwrap();
+        store.update_order(&[path("Notes.txt")]).unwrap();
+        store.update_order(&[path(&work)]).unwrap();
+        let id = store.read(&path(&memo.filename), false).unwrap().id;
+        assert!(!id.is_empty());
+        store.save("Notes", "keep me too", Some(&path(&memo.filename)), None, false, None).unwrap();
+        let versions = store.list_versions(&path("Notes.txt")).unwrap();
+        assert_eq!(versions.len(), 1);
+
+        store.delete(&path(&memo.filename)).unwrap();
+        assert_eq!(store.list().unwrap().len(), 1);
+        assert!(!store.root().join(".history").join("Notes.txt").exists());
+
+        let trashed = store.list_trash().unwrap();
+        let restored = store.restore(&trashed[0].id).unwrap();
+        assert_eq!(restored.filename, "Notes.txt");
+        assert_eq!(restored.content, "keep me too");
+        assert_eq!(restored.id, id);
+        assert_eq!(store.list_versions(&path("Notes.txt")).unwrap()[0].id, versions[0].id);
+
+        // Only the memo's own folder makes room for it
+        let order = store.read_order_data().unwrap();
+        assert_eq!((order.get("Notes.txt"), order.get(&work)), (Some(&0), Some(&0)));
+    }
+
+    #[test]
+    fn test_check_version_detects_changes_on_disk() {
+        let dir = TempDir::new("version");
+        let store = dir.store("memos");
+        let memo = store.import("Plan.md", "first").unwrap();
+        let plan = path(&memo.filename);
+        store.check_version(&plan, &memo.version).unwrap();
+
+        fs::write(store.root().join("Plan.md"), "changed elsewhere").unwrap();
+        match store.check_version(&plan, &memo.version) {
+            Err(MemoError::Conflict { current_content, current_version, .. }) => {
+                assert_eq!(current_content.as_deref(), Some("changed elsewhere"));
+                assert_eq!(current_version, Some(store.read(&plan, false).unwrap().version));
+            }
+            other => panic!("expected a conflict, got {:?}", other),
+        }
+
+        fs::remove_file(store.root().join("Plan.md")).unwrap();
+        assert!(matches!(
+            store.check_version(&plan, &memo.version),
+            Err(MemoError::Conflict { current_content: None, .. })
+        ));
+    }
+
+    #[test]
+    fn test_save_checks_and_returns_versions() {
+        let dir = TempDir::new("save-version");
+        let store = dir.store("memos");
+        let memo = store.import("Plan.md", "first").unwrap();
+
+        // Each save hands out the version the next one has to match
+        let saved = store.save("Plan", "second", Some(&path(&memo.filename)), None, false, Some(&memo.version)).unwrap();
+        assert_eq!(saved.version, store.read(&path(&saved.filename), false).unwrap().version);
+        let saved = store.save("Plan", "third", Some(&path(&saved.filename)), None, false, Some(&saved.version)).unwrap();
+
+        fs::write(store.root().join("Plan.md"), "changed elsewhere").unwrap();
+        assert!(matches!(
+            store.save("Plan", "fourth", Some(&path(&saved.filename)), None, false, Some(&saved.version)),
+            Err(MemoError::Conflict { .. })
+        ));
+        assert_eq!(fs::read_to_string(store.root().join("Plan.md")).unwrap(), "changed elsewhere");
+    }
+
+    #[test]
+    fn test_toggle_pin_returns_new_version() {
+        let dir = TempDir::new("pin-version");
+        let store = dir.store("memos");
+        let memo = store.import("Plan.md", "---\ntitle: Plan\n---\nbody\n").unwrap();
+        let plan = path(&memo.filename);
+        let read = store.read(&plan, false).unwrap();
+
+        // The pin goes into the front matter, and saving after it still works
+        let toggled = store.toggle_pin(&plan).unwrap();
+        assert!(toggled.pinned);
+        assert_ne!(toggled.version, read.version);
+        assert_eq!(toggled.version, store.read(&plan, false).unwrap().version);
+        store.save("Plan", "edited", Some(&plan), None, false, Some(&toggled.version)).unwrap();
+        assert_eq!(store.read(&plan, false).unwrap().front_matter.unwrap().pinned, Some(true));
+
+        assert!(matches!(store.toggle_pin(&path("Missing.md")), Err(MemoError::NotFound { .. })));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_memo_id_follows_rename_with_edit() {
+        let dir = TempDir::new("ids-edit");
+        let store = dir.store("memos");
+        store.import("Draft.md", "draft").unwrap();
+        store.import("Other.md", "other").unwrap();
+        assert!(store.toggle_pin(&path("Draft.md")).unwrap().pinned);
+        let id = store.read(&path("Draft.md"), false).unwrap().id;
+
+        // Renamed and rewritten in place by another program: same inode, new content
+        fs::rename(store.root().join("Draft.md"), store.root().join("Final.md")).unwrap();
+        fs::write(store.root().join("Final.md"), "final version, much longer than the draft").unwrap();
+
+        let memos = store.list().unwrap();
+        let memo = memos.iter().find(|memo| memo.filename == "Final.md").unwrap();
+        assert_eq!((memo.id.as_str(), memo.pinned), (id.as_str(), true));
+    }
+
+    #[test]
+    fn test_pins_and_order_follow_memo_ids_across_renames() {
+        let dir = TempDir::new("ids");
+        let store = dir.store("memos");
+        let alpha = store.import("Alpha.md", "alpha").unwrap();
+        let beta = store.import("Beta.md", "beta").unwrap();
+        assert!(store.toggle_pin(&path(&alpha.filename)).unwrap().pinned);
+        store.update_order(&[path("Beta.md"), path("Alpha.md")]).unwrap();
+        let id = store.read(&path("Alpha.md"), false).unwrap().id;
+        assert!(!id.is_empty());
+
+        // A title edit renames the file
+        let renamed = store.save("Renamed", "alpha", Some(&path(&alpha.filename)), None, false, None).unwrap().filename;
+        let memo = store.read(&path(&renamed), false).unwrap();
+        assert_eq!((memo.id.as_str(), memo.pinned), (id.as_str(), true));
+
+        // So does another program, which the next listing notices
+        fs::rename(store.root().join(&renamed), store.root().join("Outside.md")).unwrap();
+        let memos = store.list().unwrap();
+        assert_eq!(memos[0].filename, "Outside.md");
+        assert_eq!((memos[0].id.as_str(), memos[0].pinned), (id.as_str(), true));
+        assert_eq!(store.find_memo_by_id(&id).unwrap().as_deref(), Some("Outside.md"));
+        assert_eq!(store.read_order_data().unwrap().get("Outside.md"), Some(&1));
+
+        // Pins written before memos had IDs are keyed by filename until the next write
+        fs::write(store.root().join(".pins.json"), r#"{"Beta.md": {"pinned": true, "pinned_at": null}}"#).unwrap();
+        assert!(store.read(&path(&beta.filename), false).unwrap().pinned);
+        assert!(store.toggle_pin(&path("Outside.md")).unwrap().pinned);
+        let pins = fs::read_to_string(store.root().join(".pins.json")).unwrap();
+        assert!(!pins.contains("Beta.md"));
+        assert!(store.read(&path(&beta.filename), false).unwrap().pinned);
+    }
+}
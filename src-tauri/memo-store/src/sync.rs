@@ -0,0 +1,340 @@
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::path::Path;
+
+use crate::archive::walk_all_files;
+use crate::encryption::KEY_FILENAME;
+use crate::error::MemoError;
+use crate::store::{conflict_copy_filename, content_hash, MemoStore};
+use crate::utils::atomic::atomic_write;
+use crate::utils::paths::{ensure_dir_exists, is_memo_file, MemoPath};
+
+/// Last-sync manifests of a memo directory, one per directory it syncs with
+const SYNC_STATE_FILE: &str = ".sync-state.json";
+
+/// What both directories looked like after their last sync. A file that
+/// differs from it changed on that side since.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SyncBase {
+    synced_at: String,
+    /// Content hashes of the synced files
+    files: HashMap<String, String>,
+    pins: HashMap<String, Value>,
+    order: HashMap<String, Value>,
+}
+
+/// A memo changed in both directories. The local version stays in place on
+/// both sides and the other one is kept next to it as a conflict copy.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncConflict {
+    pub filename: String,
+    pub conflict_copy: String,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncReport {
+    /// Files copied from the other directory into this one
+    pub pulled: Vec<String>,
+    /// Files copied from this directory into the other one
+    pub pushed: Vec<String>,
+    /// Files deleted here because they were deleted there; memos go to the trash
+    pub deleted_local: Vec<String>,
+    /// Files deleted there because they were deleted here; memos go to its trash
+    pub deleted_remote: Vec<String>,
+    pub conflicts: Vec<SyncConflict>,
+    pub warnings: Vec<String>,
+}
+
+/// Content hashes of the files that are synced: memos and attachments, but
+/// nothing hidden, so the trash, history and app metadata stay per directory
+fn hash_files(dir: &Path) -> Result<HashMap<String, String>, MemoError> {
+    let files = walk_all_files(dir).map_err(|e| MemoError::io("Failed to read memo directory", dir, e))?;
+    let mut hashes = HashMap::new();
+
+    for (relative, path) in files {
+        if relative.split('/').any(|part| part.starts_with('.')) {
+            continue;
+        }
+        let contents = fs::read(&path).map_err(|e| MemoError::io("Failed to read file", &path, e))?;
+        hashes.insert(relative, content_hash(&contents));
+    }
+    Ok(hashes)
+}
+
+fn read_file(root: &Path, relative: &str) -> Result<Vec<u8>, MemoError> {
+    let path = root.join(relative);
+    fs::read(&path).map_err(|e| MemoError::io("Failed to read file", &path, e))
+}
+
+fn write_file(root: &Path, relative: &str, contents: &[u8]) -> Result<(), MemoError> {
+    let path = root.join(relative);
+    if let Some(parent) = path.parent() {
+        ensure_dir_exists(&parent.to_path_buf())?;
+    }
+    atomic_write(&path, contents).map_err(|e| MemoError::io("Failed to write file", &path, e))
+}
+
+fn to_values<T: Serialize>(map: &HashMap<String, T>) -> Result<HashMap<String, Value>, MemoError> {
+    serde_json::to_value(map)
+        .and_then(serde_json::from_value)
+        .map_err(|e| MemoError::format("Failed to serialize metadata", None, e))
+}
+
+fn from_values<T: DeserializeOwned>(map: HashMap<String, Value>) -> Result<HashMap<String, T>, MemoError> {
+    serde_json::from_value(Value::Object(map.into_iter().collect()))
+        .map_err(|e| MemoError::format("Failed to parse metadata", None, e))
+}
+
+/// Three-way merge of `.pins.json` or `.order.json` entries: a side that
+/// still has the base value takes the other side's change, and when both
+/// changed this directory wins. Entries of files that are gone are dropped.
+fn merge_metadata(
+    local: &HashMap<String, Value>,
+    remote: &HashMap<String, Value>,
+    base: &HashMap<String, Value>,
+    keep: impl Fn(&str) -> bool,
+) -> HashMap<String, Value> {
+    let keys: BTreeSet<&String> = local.keys().chain(remote.keys()).collect();
+    let mut merged = HashMap::new();
+
+    for key in keys {
+        let (l, r) = (local.get(key), remote.get(key));
+        let value = if l == base.get(key) { r } else { l };
+        if let Some(value) = value.filter(|_| keep(key)) {
+            merged.insert(key.clone(), value.clone());
+        }
+    }
+    merged
+}
+
+impl MemoStore {
+    fn read_sync_state(&self) -> Result<HashMap<String, SyncBase>, MemoError> {
+        let path = self.root().join(SYNC_STATE_FILE);
+        if !path.exists() {
+            return Ok(HashMap::new());
+        }
+        let content = fs::read_to_string(&path).map_err(|e| MemoError::io("Failed to read sync state", &path, e))?;
+        serde_json::from_str(&content).map_err(|e| MemoError::format("Failed to parse sync state", Some(&path), e))
+    }
+
+    fn write_sync_state(&self, state: &HashMap<String, SyncBase>) -> Result<(), MemoError> {
+        let path = self.root().join(SYNC_STATE_FILE);
+        let content = serde_json::to_string_pretty(state)
+            .map_err(|e| MemoError::format("Failed to serialize sync state", None, e))?;
+        atomic_write(&path, content).map_err(|e| MemoError::io("Failed to write sync state", &path, e))
+    }
+
+    /// Delete a file that was deleted in the other directory. Memos go to the
+    /// trash so a sync never loses one for good.
+    fn remove_synced_file(&self, relative: &str) -> Result<(), MemoError> {
+        if is_memo_file(Path::new(relative)) {
+            return self.delete(&MemoPath::parse(relative)?);
+        }
+        let path = self.root().join(relative);
+        fs::remove_file(&path).map_err(|e| MemoError::io("Failed to delete file", &path, e))?;
+        self.mark_change();
+        Ok(())
+    }
+
+    /// Reconcile this memo directory with `other_dir`, e.g. a copy on a USB
+    /// drive or a NAS. Changes on either side since the last sync between the
+    /// two are copied across, deletions are carried over into the trash, and
+    /// pins and sidebar order are merged. A file changed on both sides keeps
+    /// this directory's version, with the other one next to it as
+    /// `name (conflict YYYY-MM-DD).md` in both directories.
+    pub fn sync_with(&self, other_dir: &Path) -> Result<SyncReport, MemoError> {
+        let other = MemoStore::open(other_dir)?;
+        let canonical = |dir: &Path| fs::canonicalize(dir).map_err(|e| MemoError::io("Failed to resolve directory", dir, e));
+        let here = canonical(self.root())?;
+        let there = canonical(other.root())?;
+        if here.starts_with(&there) || there.starts_with(&here) {
+            return Err(MemoError::invalid_input(
+                "A memo directory can't be synced with itself or a folder inside it".to_string(),
+            ));
+        }
+
+        let key = there.to_string_lossy().to_string();
+        let mut state = self.read_sync_state()?;
+        let base = state.remove(&key).unwrap_or_default();
+        let local = hash_files(self.root())?;
+        let remote = hash_files(other.root())?;
+
+        let mut report = SyncReport::default();
+        let mut synced = HashMap::new();
+        let mut both_changed = Vec::new();
+
+        let paths: BTreeSet<&String> = local.keys().chain(remote.keys()).chain(base.files.keys()).collect();
+        for path in paths {
+            let (l, r, b) = (local.get(path), remote.get(path), base.files.get(path));
+            if l == r {
+                if let Some(hash) = l {
+                    synced.insert(path.clone(), hash.clone());
+                }
+                continue;
+            }
+
+            // The side that still matches the last sync takes the other's
+            // change. When both changed, an edit wins over a deletion.
+            let local_changed = l != b;
+            let remote_changed = r != b;
+            match (l, r) {
+                (_, Some(hash)) if !local_changed || (l.is_none() && remote_changed) => {
+                    write_file(self.root(), path, &read_file(other.root(), path)?)?;
+                    self.mark_change();
+                    synced.insert(path.clone(), hash.clone());
+                    report.pulled.push(path.clone());
+                }
+                (Some(hash), _) if !remote_changed || (r.is_none() && local_changed) => {
+                    write_file(other.root(), path, &read_file(self.root(), path)?)?;
+                    synced.insert(path.clone(), hash.clone());
+                    report.pushed.push(path.clone());
+                }
+                (Some(_), None) => {
+                    self.remove_synced_file(path)?;
+                    report.deleted_local.push(path.clone());
+                }
+                (None, Some(_)) => {
+                    other.remove_synced_file(path)?;
+                    report.deleted_remote.push(path.clone());
+                }
+                _ => both_changed.push(path.clone()),
+            }
+        }
+
+        // Both directories now hold the same files apart from these, so a
+        // conflict copy name that is free here is free there too
+        let label = chrono::Local::now().format("%Y-%m-%d").to_string();
+        for path in both_changed {
+            let ours = read_file(self.root(), &path)?;
+            let theirs = read_file(other.root(), &path)?;
+            let conflict_copy = conflict_copy_filename(self.root(), &path, &label)?;
+
+            write_file(self.root(), &conflict_copy, &theirs)?;
+            self.mark_change();
+            write_file(other.root(), &conflict_copy, &theirs)?;
+            write_file(other.root(), &path, &ours)?;
+            synced.insert(path.clone(), content_hash(&ours));
+            synced.insert(conflict_copy.clone(), content_hash(&theirs));
+            report.conflicts.push(SyncConflict {
+                filename: path,
+                conflict_copy,
+            });
+        }
+
+        let keep = |filename: &str| synced.contains_key(filename);
+        let local_pins = to_values(&self.read_pin_data()?)?;
+        let pins = merge_metadata(&local_pins, &to_values(&other.read_pin_data()?)?, &base.pins, keep);
+        self.write_pin_data(&from_values(pins.clone())?)?;
+        other.write_pin_data(&from_values(pins.clone())?)?;
+
+        let local_order = to_values(&self.read_order_data()?)?;
+        let order = merge_metadata(&local_order, &to_values(&other.read_order_data()?)?, &base.order, keep);
+        self.write_order_data(&from_values(order.clone())?)?;
+        other.write_order_data(&from_values(order.clone())?)?;
+
+        // Encrypted memos only open with the key file they were written with
+        match (fs::read(self.root().join(KEY_FILENAME)).ok(), fs::read(other.root().join(KEY_FILENAME)).ok()) {
+            (Some(ours), None) => write_file(other.root(), KEY_FILENAME, &ours)?,
+            (None, Some(theirs)) => {
+                write_file(self.root(), KEY_FILENAME, &theirs)?;
+                self.mark_change();
+            }
+            (Some(ours), Some(theirs)) if ours != theirs => report.warnings.push(
+                "The directories have different passphrases; encrypted memos from the other one won't open here"
+                    .to_string(),
+            ),
+            _ => {}
+        }
+
+        state.insert(
+            key,
+            SyncBase {
+                synced_at: chrono::Utc::now().to_rfc3339(),
+                files: synced,
+                pins,
+                order,
+            },
+        );
+        self.write_sync_state(&state)?;
+
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::TempDir;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn test_sync_copies_changes_and_keeps_both_sides_of_a_conflict() {
+        let dir = TempDir::new("sync");
+        let laptop = dir.store("laptop");
+        let usb = dir.store("usb");
+
+        let plan = MemoPath::parse(&laptop.import("Plan.md", "first").unwrap().filename).unwrap();
+        let notes = MemoPath::parse(&usb.import("Notes.md", "notes").unwrap().filename).unwrap();
+        usb.toggle_pin(&notes).unwrap();
+
+        let report = laptop.sync_with(usb.root()).unwrap();
+        assert_eq!(report.pushed, vec!["Plan.md"]);
+        assert_eq!(report.pulled, vec!["Notes.md"]);
+        assert!(laptop.read(&notes, false).unwrap().pinned);
+
+        // One-sided edits and deletions follow the changed side
+        usb.save("Plan", "second", Some(&plan), None, false, None).unwrap();
+        laptop.delete(&notes).unwrap();
+        let report = laptop.sync_with(usb.root()).unwrap();
+        assert_eq!(report.pulled, vec!["Plan.md"]);
+        assert_eq!(report.deleted_remote, vec!["Notes.md"]);
+        assert_eq!(laptop.read(&plan, false).unwrap().content, "second");
+        assert_eq!(usb.list_trash().unwrap().len(), 1);
+
+        laptop.save("Plan", "from laptop", Some(&plan), None, false, None).unwrap();
+        usb.save("Plan", "from usb", Some(&plan), None, false, None).unwrap();
+        let report = laptop.sync_with(usb.root()).unwrap();
+        assert_eq!(report.conflicts.len(), 1);
+        let copy = MemoPath::parse(&report.conflicts[0].conflict_copy).unwrap();
+        for store in [&laptop, &usb] {
+            assert_eq!(store.read(&plan, false).unwrap().content, "from laptop");
+            assert_eq!(store.read(&copy, false).unwrap().content, "from usb");
+        }
+
+        let report = laptop.sync_with(usb.root()).unwrap();
+        assert!(report.pulled.is_empty() && report.pushed.is_empty() && report.conflicts.is_empty());
+        assert!(laptop.sync_with(&dir.join("laptop")).is_err());
+    }
+
+    static CHANGES: AtomicUsize = AtomicUsize::new(0);
+
+    fn count_change(_: &Path) {
+        CHANGES.fetch_add(1, Ordering::SeqCst);
+    }
+
+    #[test]
+    fn test_sync_reports_pulled_files_and_conflict_copies_as_changes() {
+        let dir = TempDir::new("sync-changes");
+        let laptop = dir.store("laptop").with_change_hook(count_change);
+        let usb = dir.store("usb");
+
+        let plan = MemoPath::parse(&usb.import("Plan.md", "first").unwrap().filename).unwrap();
+        laptop.sync_with(usb.root()).unwrap();
+        assert!(CHANGES.swap(0, Ordering::SeqCst) > 0);
+
+        usb.save("Plan", "from usb", Some(&plan), None, false, None).unwrap();
+        fs::write(laptop.root().join("Plan.md"), "from laptop").unwrap();
+        let report = laptop.sync_with(usb.root()).unwrap();
+        assert_eq!(report.conflicts.len(), 1);
+        assert!(CHANGES.swap(0, Ordering::SeqCst) > 0);
+
+        laptop.sync_with(usb.root()).unwrap();
+        assert_eq!(CHANGES.load(Ordering::SeqCst), 0);
+    }
+}
@@ -0,0 +1,52 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+
+use crate::error::MemoError;
+use crate::store::{MemoMetadata, MemoStore};
+use crate::utils::tags::{tag_matches, tag_with_ancestors};
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TagCount {
+    pub name: String,
+    /// Memos tagged with this tag or any tag nested below it
+    pub count: usize,
+}
+
+impl MemoStore {
+    /// List every tag in use with the number of memos carrying it. Parents of
+    /// hierarchical tags are listed too, so `#work/project-x` also counts for `work`.
+    pub fn list_tags(&self) -> Result<Vec<TagCount>, MemoError> {
+        let mut counts: BTreeMap<String, TagCount> = BTreeMap::new();
+
+        for memo in self.list()? {
+            // A memo tagged both `work` and `work/a` still counts once for `work`
+            let mut seen = HashSet::new();
+            for tag in &memo.tags {
+                for name in tag_with_ancestors(tag) {
+                    let key = name.to_lowercase();
+                    if !seen.insert(key.clone()) {
+                        continue;
+                    }
+                    counts
+                        .entry(key)
+                        .or_insert_with(|| TagCount {
+                            name: name.to_string(),
+                            count: 0,
+                        })
+                        .count += 1;
+                }
+            }
+        }
+
+        Ok(counts.into_values().collect())
+    }
+
+    /// List memos carrying `tag` or a tag nested below it, in sidebar order
+    pub fn memos_by_tag(&self, tag: &str) -> Result<Vec<MemoMetadata>, MemoError> {
+        Ok(self.list()?
+            .into_iter()
+            .filter(|memo| memo.tags.iter().any(|t| tag_matches(t, tag)))
+            .collect())
+    }
+}
@@ -0,0 +1,265 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::error::MemoError;
+use crate::history::{stash_history, unstash_history};
+use crate::search;
+use crate::store::{resolve_unique_filename, MemoMetadata, MemoStore, PinData};
+use crate::utils::atomic::atomic_write;
+use crate::utils::paths::{ensure_dir_exists, join_memo_path, memo_folder, memo_title};
+
+/// A deleted memo waiting in `.trash`, with what is needed to put it back
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TrashEntry {
+    pub id: String,
+    pub filename: String,
+    pub title: String,
+    pub deleted_at: String,
+    pub size: u64,
+    pub pinned: bool,
+    pub pinned_at: Option<String>,
+    pub order: Option<usize>,
+    /// The memo's stable ID, given back when it is restored
+    #[serde(default)]
+    pub memo_id: Option<String>,
+}
+
+/// Get the trash directory path inside the memo directory
+fn get_trash_dir(memo_dir: &Path) -> PathBuf {
+    memo_dir.join(".trash")
+}
+
+/// Get the trash manifest path
+fn get_trash_manifest(memo_dir: &Path) -> PathBuf {
+    get_trash_dir(memo_dir).join(".trash.json")
+}
+
+/// Get the directory holding the history of a trashed memo
+fn get_trashed_history(memo_dir: &Path, id: &str) -> PathBuf {
+    get_trash_dir(memo_dir).join(format!("{}.history", id))
+}
+
+/// Read trash entries from the manifest
+pub(crate) fn read_trash_entries(memo_dir: &Path) -> Result<Vec<TrashEntry>, MemoError> {
+    let manifest = get_trash_manifest(memo_dir);
+
+    if !manifest.exists() {
+        return Ok(Vec::new());
+    }
+
+    let content = fs::read_to_string(&manifest)
+        .map_err(|e| MemoError::io("Failed to read trash manifest", &manifest, e))?;
+
+    serde_json::from_str(&content).map_err(|e| MemoError::format("Failed to parse trash manifest", Some(&manifest), e))
+}
+
+/// Write trash entries to the manifest
+pub(crate) fn write_trash_entries(memo_dir: &Path, entries: &[TrashEntry]) -> Result<(), MemoError> {
+    let manifest = get_trash_manifest(memo_dir);
+    let content = serde_json::to_string_pretty(entries)
+        .map_err(|e| MemoError::format("Failed to serialize trash manifest", None, e))?;
+
+    atomic_write(&manifest, content)
+        .map_err(|e| MemoError::io("Failed to write trash manifest", &manifest, e))?;
+
+    Ok(())
+}
+
+/// Permanently remove the given entries' files from the trash
+fn remove_trashed_files(memo_dir: &Path, entries: &[TrashEntry]) -> Result<(), MemoError> {
+    let trash_dir = get_trash_dir(memo_dir);
+    for entry in entries {
+        let path = trash_dir.join(&entry.id);
+        if path.exists() {
+            fs::remove_file(&path)
+                .map_err(|e| MemoError::io("Failed to delete trashed memo", &path, e))?;
+        }
+        let history = get_trashed_history(memo_dir, &entry.id);
+        if history.exists() {
+            fs::remove_dir_all(&history)
+                .map_err(|e| MemoError::io("Failed to delete trashed memo history", &history, e))?;
+        }
+    }
+    Ok(())
+}
+
+impl MemoStore {
+    /// Move a memo into the trash, recording its pin state and position; its
+    /// history goes along
+    pub(crate) fn move_to_trash(&self, filename: &str) -> Result<TrashEntry, MemoError> {
+        let memo_dir = self.root();
+        let trash_dir = get_trash_dir(memo_dir);
+        ensure_dir_exists(&trash_dir)?;
+        self.purge_expired()?;
+        let mut entries = read_trash_entries(memo_dir)?;
+
+        let source = memo_dir.join(filename);
+        let size = fs::metadata(&source)
+            .map_err(|e| MemoError::io("Failed to read metadata", &source, e))?
+            .len();
+
+        let now = chrono::Utc::now();
+        // The timestamp keeps repeated deletions of the same filename apart
+        let id = format!("{}_{}", now.format("%Y%m%dT%H%M%S%3f"), filename.replace('/', "_"));
+
+        self.mark_write(&source);
+        fs::rename(&source, trash_dir.join(&id))
+            .map_err(|e| MemoError::io("Failed to move memo to trash", &source, e))?;
+        search::remove_memo(memo_dir, filename);
+        stash_history(memo_dir, filename, &get_trashed_history(memo_dir, &id))?;
+
+        let mut pins = self.read_pin_data().unwrap_or_default();
+        let pin_info = pins.remove(filename);
+        if pin_info.is_some() {
+            self.write_pin_data(&pins)?;
+        }
+
+        let mut order = self.read_order_data().unwrap_or_default();
+        let position = order.remove(filename);
+        if position.is_some() {
+            self.write_order_data(&order)?;
+        }
+        // A memo taking the name later must not inherit the ID; the entry
+        // keeps it for a restore
+        let memo_id = Some(self.memo_id(filename)).filter(|id| !id.is_empty());
+        self.remove_memo_id(filename)?;
+
+        let entry = TrashEntry {
+            id,
+            filename: filename.to_string(),
+            title: memo_title(filename).to_string(),
+            deleted_at: now.to_rfc3339(),
+            size,
+            pinned: pin_info.as_ref().map(|p| p.pinned).unwrap_or(false),
+            pinned_at: pin_info.and_then(|p| p.pinned_at),
+            order: position,
+            memo_id,
+        };
+
+        entries.push(entry.clone());
+        write_trash_entries(memo_dir, &entries)?;
+
+        Ok(entry)
+    }
+
+    /// Drop entries older than the configured retention period (0 keeps them forever)
+    fn purge_expired(&self) -> Result<(), MemoError> {
+        let memo_dir = self.root();
+        let retention_days = self.trash_retention_days();
+        if retention_days == 0 {
+            return Ok(());
+        }
+
+        let cutoff = chrono::Utc::now() - chrono::Duration::days(retention_days as i64);
+        let (expired, kept): (Vec<TrashEntry>, Vec<TrashEntry>) = read_trash_entries(memo_dir)?
+            .into_iter()
+            .partition(|entry| {
+                chrono::DateTime::parse_from_rfc3339(&entry.deleted_at)
+                    .map(|deleted_at| deleted_at < cutoff)
+                    .unwrap_or(false)
+            });
+
+        if expired.is_empty() {
+            return Ok(());
+        }
+
+        remove_trashed_files(memo_dir, &expired)?;
+        write_trash_entries(memo_dir, &kept)
+    }
+
+    /// List trashed memos, most recently deleted first
+    pub fn list_trash(&self) -> Result<Vec<TrashEntry>, MemoError> {
+        self.purge_expired()?;
+
+        let mut entries = read_trash_entries(self.root())?;
+        entries.sort_by(|a, b| b.deleted_at.cmp(&a.deleted_at));
+        Ok(entries)
+    }
+
+    /// Put a trashed memo back where it was, with its pin, position and
+    /// history. An encrypted memo restored while the vault is locked comes
+    /// back without title and content, as `list` shows it.
+    pub fn restore(&self, id: &str) -> Result<MemoMetadata, MemoError> {
+        let memo_dir = self.root();
+        let mut entries = read_trash_entries(memo_dir)?;
+
+        let index = entries
+            .iter()
+            .position(|entry| entry.id == id)
+            .ok_or_else(|| MemoError::not_found(format!("Trashed memo '{}' not found", id), &get_trash_dir(memo_dir)))?;
+        let entry = entries.remove(index);
+
+        // Another memo may have taken the name in the meantime, and its folder
+        // may be gone
+        let path = Path::new(&entry.filename);
+        let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("restored_memo");
+        let ext = path.extension().and_then(|s| s.to_str()).unwrap_or("md");
+        let folder = memo_folder(&entry.filename);
+        ensure_dir_exists(&memo_dir.join(folder))?;
+        let filename = join_memo_path(folder, &resolve_unique_filename(&memo_dir.join(folder), stem, ext)?);
+        let target_path = memo_dir.join(&filename);
+
+        self.mark_write(&target_path);
+        fs::rename(get_trash_dir(memo_dir).join(&entry.id), &target_path)
+            .map_err(|e| MemoError::io("Failed to restore memo", &target_path, e))?;
+        write_trash_entries(memo_dir, &entries)?;
+        unstash_history(memo_dir, &get_trashed_history(memo_dir, &entry.id), &filename)?;
+        if let Some(memo_id) = &entry.memo_id {
+            self.restore_memo_ids(&[(memo_id.clone(), filename.clone())])?;
+        }
+
+        if entry.pinned {
+            let mut pins = self.read_pin_data().unwrap_or_default();
+            pins.insert(
+                filename.clone(),
+                PinData {
+                    pinned: true,
+                    pinned_at: entry.pinned_at.clone(),
+                },
+            );
+            self.write_pin_data(&pins)?;
+        }
+
+        if let Some(position) = entry.order {
+            // Make room at the old position in the memo's folder instead of
+            // sharing it with another memo
+            let mut order = self.read_order_data().unwrap_or_default();
+            for (other, index) in order.iter_mut() {
+                if memo_folder(other) == folder && *index >= position {
+                    *index += 1;
+                }
+            }
+            order.insert(filename.clone(), position);
+            self.write_order_data(&order)?;
+        }
+
+        let content = fs::read_to_string(&target_path)
+            .map_err(|e| MemoError::io("Failed to read file", &target_path, e))?;
+        search::index_memo(memo_dir, &filename, &content);
+
+        self.load_or_locked(&filename)
+    }
+
+    /// Permanently delete one trashed memo, or the whole trash when `id` is omitted.
+    /// Returns the number of memos removed.
+    pub fn purge_trash(&self, id: Option<&str>) -> Result<usize, MemoError> {
+        let memo_dir = self.root();
+
+        let (purged, kept): (Vec<TrashEntry>, Vec<TrashEntry>) = read_trash_entries(memo_dir)?
+            .into_iter()
+            .partition(|entry| id.map(|id| entry.id == id).unwrap_or(true));
+
+        if let Some(id) = id {
+            if purged.is_empty() {
+                return Err(MemoError::not_found(format!("Trashed memo '{}' not found", id), &get_trash_dir(memo_dir)));
+            }
+        }
+
+        remove_trashed_files(memo_dir, &purged)?;
+        write_trash_entries(memo_dir, &kept)?;
+
+        Ok(purged.len())
+    }
+}
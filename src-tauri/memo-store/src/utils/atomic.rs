@@ -0,0 +1,78 @@
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Distinguishes temp files of concurrent writes from the same process
+static TEMP_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Write a file so that readers only ever see the old or the new content.
+///
+/// The data goes to a hidden temp file in the same directory, is flushed to
+/// disk, and then renamed over the target, so a crash mid-write leaves the
+/// previous file intact instead of a truncated one.
+pub fn atomic_write(path: &Path, contents: impl AsRef<[u8]>) -> io::Result<()> {
+    let dir = path.parent().unwrap_or_else(|| Path::new("."));
+    let file_name = path
+        .file_name()
+        .and_then(|f| f.to_str())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Invalid file name"))?;
+
+    let temp_path = dir.join(format!(
+        ".{}.{}-{}.tmp",
+        file_name,
+        std::process::id(),
+        TEMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+
+    let result = File::create(&temp_path)
+        .and_then(|mut file| {
+            file.write_all(contents.as_ref())?;
+            file.sync_all()
+        })
+        .and_then(|_| fs::rename(&temp_path, path));
+
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+        return result;
+    }
+
+    // Make the rename itself durable; directories can't be opened for this on Windows
+    #[cfg(unix)]
+    if let Ok(dir) = File::open(dir) {
+        let _ = dir.sync_all();
+    }
+
+    Ok(())
+}
+
+/// Path of the last known good copy of a metadata file (`.pins.json` -> `.pins.json.bak`)
+pub fn backup_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().map(|f| f.to_os_string()).unwrap_or_default();
+    name.push(".bak");
+    path.with_file_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::TempDir;
+
+    #[test]
+    fn test_atomic_write_replaces_content() {
+        let dir = TempDir::new("atomic");
+        let path = dir.join("memo.md");
+
+        atomic_write(&path, "first").unwrap();
+        atomic_write(&path, "second").unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "second");
+        // No temp files are left behind
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_backup_path() {
+        assert_eq!(backup_path(Path::new("/memos/.pins.json")), PathBuf::from("/memos/.pins.json.bak"));
+    }
+}
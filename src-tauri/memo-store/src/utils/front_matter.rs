@@ -0,0 +1,183 @@
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::BTreeMap;
+
+use crate::error::MemoError;
+
+/// Metadata stored in a YAML block at the top of a Markdown memo:
+///
+/// ```text
+/// ---
+/// title: Weekly review
+/// tags: [work, review]
+/// pinned: true
+/// ---
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FrontMatter {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, deserialize_with = "string_or_list", skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pinned: Option<bool>,
+    #[serde(default, deserialize_with = "string_or_list", skip_serializing_if = "Vec::is_empty")]
+    pub aliases: Vec<String>,
+    /// Keys the app doesn't know about, kept so rewriting doesn't drop them
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, serde_yaml::Value>,
+}
+
+impl FrontMatter {
+    pub fn is_empty(&self) -> bool {
+        *self == FrontMatter::default()
+    }
+}
+
+/// Accept both `tags: [a, b]` and the shorthand `tags: a, b`
+fn string_or_list<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum StringOrList {
+        String(String),
+        List(Vec<String>),
+        Null(()),
+    }
+
+    Ok(match StringOrList::deserialize(deserializer)? {
+        StringOrList::String(value) => value
+            .split(',')
+            .map(|item| item.trim().to_string())
+            .filter(|item| !item.is_empty())
+            .collect(),
+        StringOrList::List(values) => values,
+        StringOrList::Null(()) => Vec::new(),
+    })
+}
+
+/// Split raw file content into the YAML between the `---` fences and the body.
+/// Returns `None` for the YAML when the content doesn't start with a closed block.
+pub fn split_front_matter(content: &str) -> (Option<&str>, &str) {
+    let rest = match content
+        .strip_prefix("---\n")
+        .or_else(|| content.strip_prefix("---\r\n"))
+    {
+        Some(rest) => rest,
+        None => return (None, content),
+    };
+
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n') {
+        let trimmed = line.trim_end_matches(['\r', '\n']);
+        if trimmed == "---" || trimmed == "..." {
+            return (Some(&rest[..offset]), &rest[offset + line.len()..]);
+        }
+        offset += line.len();
+    }
+
+    (None, content)
+}
+
+/// Parse the front matter of a memo and return it with the body that follows.
+/// Content whose leading block isn't a YAML mapping is returned untouched as body.
+pub fn parse_front_matter(content: &str) -> (Option<FrontMatter>, &str) {
+    match split_front_matter(content) {
+        (Some(yaml), body) => match serde_yaml::from_str::<FrontMatter>(yaml) {
+            Ok(front_matter) => (Some(front_matter), body),
+            // An empty block (`---\n---`) parses as null rather than a mapping
+            Err(_) if yaml.trim().is_empty() => (Some(FrontMatter::default()), body),
+            Err(_) => (None, content),
+        },
+        (None, body) => (None, body),
+    }
+}
+
+/// Join front matter and body back into file content
+pub fn render_front_matter(front_matter: &FrontMatter, body: &str) -> Result<String, MemoError> {
+    if front_matter.is_empty() {
+        return Ok(body.to_string());
+    }
+
+    let yaml = serde_yaml::to_string(front_matter)
+        .map_err(|e| MemoError::format("Failed to serialize front matter", None, e))?;
+
+    Ok(format!("---\n{}---\n{}", yaml, body))
+}
+
+/// Turn a front matter date such as `2024-05-01`, `2024-05-01 09:30` or an
+/// RFC 3339 timestamp into RFC 3339, reading dates without an offset as local time
+pub fn normalize_timestamp(value: &str) -> Option<String> {
+    use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, TimeZone, Utc};
+
+    let value = value.trim();
+    if let Ok(parsed) = DateTime::parse_from_rfc3339(value) {
+        return Some(parsed.with_timezone(&Utc).to_rfc3339());
+    }
+
+    let naive = ["%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M", "%Y-%m-%dT%H:%M:%S", "%Y-%m-%dT%H:%M"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+        .or_else(|| {
+            NaiveDate::parse_from_str(value, "%Y-%m-%d")
+                .ok()
+                .and_then(|date| date.and_hms_opt(0, 0, 0))
+        })?;
+
+    Local
+        .from_local_datetime(&naive)
+        .earliest()
+        .map(|local| local.with_timezone(&Utc).to_rfc3339())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_front_matter() {
+        let content = "---\ntitle: Plan\ntags: work, review\npinned: true\nstatus: draft\n---\n# Plan\nbody\n";
+        let (front_matter, body) = parse_front_matter(content);
+        let front_matter = front_matter.unwrap();
+
+        assert_eq!(body, "# Plan\nbody\n");
+        assert_eq!(front_matter.title.as_deref(), Some("Plan"));
+        assert_eq!(front_matter.tags, vec!["work", "review"]);
+        assert_eq!(front_matter.pinned, Some(true));
+        assert!(front_matter.extra.contains_key("status"));
+    }
+
+    #[test]
+    fn test_content_without_front_matter_is_body() {
+        assert_eq!(parse_front_matter("# Title\n---\ntext"), (None, "# Title\n---\ntext"));
+        assert_eq!(parse_front_matter("---\nunclosed"), (None, "---\nunclosed"));
+        // A horizontal rule pair around plain text is not front matter
+        assert_eq!(parse_front_matter("---\njust text\n---\n").0, None);
+    }
+
+    #[test]
+    fn test_render_round_trip() {
+        let content = "---\ntags:\n- a\naliases:\n- Other name\nstatus: draft\n---\nbody";
+        let (front_matter, body) = parse_front_matter(content);
+        let rendered = render_front_matter(&front_matter.unwrap(), body).unwrap();
+
+        assert_eq!(rendered, content);
+        assert_eq!(render_front_matter(&FrontMatter::default(), "body").unwrap(), "body");
+    }
+
+    #[test]
+    fn test_normalize_timestamp() {
+        assert_eq!(
+            normalize_timestamp("2024-05-01T09:30:00+09:00").as_deref(),
+            Some("2024-05-01T00:30:00+00:00")
+        );
+        assert!(normalize_timestamp("2024-05-01").is_some());
+        assert!(normalize_timestamp("yesterday").is_none());
+    }
+}
@@ -0,0 +1,161 @@
+use std::ops::Range;
+
+/// A `[[target]]` link in a memo. Labels (`[[target|label]]`) and headings
+/// (`[[target#heading]]`) are not part of the target.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WikiLink {
+    pub target: String,
+    pub line_number: usize,
+}
+
+/// Where a wiki link sits in a memo
+struct LinkSpan {
+    /// The whole link including the brackets
+    link: Range<usize>,
+    /// Just the target
+    target: Range<usize>,
+    line_number: usize,
+}
+
+/// Every wiki link in `content`. Links in code blocks and inline code are skipped.
+fn link_spans(content: &str) -> Vec<LinkSpan> {
+    let mut spans = Vec::new();
+    let mut in_code_block = false;
+    let mut offset = 0;
+
+    for (index, line) in content.split_inclusive('\n').enumerate() {
+        let line_start = offset;
+        offset += line.len();
+
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_code_block = !in_code_block;
+            continue;
+        }
+        if in_code_block {
+            continue;
+        }
+
+        let bytes = line.as_bytes();
+        let mut in_code_span = false;
+        let mut i = 0;
+        while i < bytes.len() {
+            if bytes[i] == b'`' {
+                in_code_span = !in_code_span;
+            } else if !in_code_span && bytes[i..].starts_with(b"[[") {
+                let inner_start = i + 2;
+                let inner_len = match line[inner_start..].find("]]") {
+                    Some(len) => len,
+                    None => break,
+                };
+                let inner = &line[inner_start..inner_start + inner_len];
+
+                if !inner.contains('[') {
+                    let target = &inner[..inner.find(['|', '#']).unwrap_or(inner.len())];
+                    let leading = target.len() - target.trim_start().len();
+                    let target = target.trim();
+                    if !target.is_empty() {
+                        let start = line_start + inner_start + leading;
+                        spans.push(LinkSpan {
+                            link: line_start + i..line_start + inner_start + inner_len + 2,
+                            target: start..start + target.len(),
+                            line_number: index + 1,
+                        });
+                    }
+                }
+                i = inner_start + inner_len + 2;
+                continue;
+            }
+            i += 1;
+        }
+    }
+
+    spans
+}
+
+/// Find the wiki links in a memo
+pub fn extract_links(content: &str) -> Vec<WikiLink> {
+    link_spans(content)
+        .into_iter()
+        .map(|span| WikiLink {
+            target: content[span.target].to_string(),
+            line_number: span.line_number,
+        })
+        .collect()
+}
+
+/// Normalize a link target or memo name for comparison: `Work/Plan.md` -> `work/plan`
+pub fn link_key(target: &str) -> String {
+    let target = target.trim().replace('\\', "/");
+    let target = target.trim_end_matches(".md").trim_end_matches(".txt");
+    target.to_lowercase()
+}
+
+/// Replace link targets for which `rename` returns a new target, keeping
+/// labels and headings. Returns None when no link changed.
+pub fn rewrite_links(content: &str, rename: impl Fn(&str) -> Option<String>) -> Option<String> {
+    let mut rewritten = content.to_string();
+    let mut changed = false;
+
+    // Replace from the end so earlier ranges stay valid
+    for span in link_spans(content).into_iter().rev() {
+        if let Some(target) = rename(&content[span.target.clone()]) {
+            rewritten.replace_range(span.target, &target);
+            changed = true;
+        }
+    }
+
+    if changed {
+        Some(rewritten)
+    } else {
+        None
+    }
+}
+
+/// Replace every wiki link, brackets included, with what `render` makes of
+/// its target and label. The label is the text after `|`, or the link text.
+pub fn replace_links(content: &str, render: impl Fn(&str, &str) -> String) -> String {
+    let mut replaced = content.to_string();
+
+    for span in link_spans(content).into_iter().rev() {
+        let inner = &content[span.link.start + 2..span.link.end - 2];
+        let label = inner.split_once('|').map_or(inner, |(_, label)| label).trim();
+        let text = render(&content[span.target], label);
+        replaced.replace_range(span.link, &text);
+    }
+
+    replaced
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract_links() {
+        let content = "See [[Plan]] and [[work/Notes|notes]].\n`[[code]]`\n```\n[[fenced]]\n```\n[[ Review #Q1 ]] [[]]\n日本の[[計画]]";
+        let links: Vec<(String, usize)> = extract_links(content)
+            .into_iter()
+            .map(|link| (link.target, link.line_number))
+            .collect();
+
+        assert_eq!(
+            links,
+            vec![("Plan".to_string(), 1), ("work/Notes".to_string(), 1), ("Review".to_string(), 6), ("計画".to_string(), 7)]
+        );
+    }
+
+    #[test]
+    fn test_rewrite_links() {
+        let content = "[[Plan]], [[plan|the plan]], [[Plan#Goals]], [[Other]]";
+        let rewritten = rewrite_links(content, |target| {
+            (link_key(target) == "plan").then(|| "Roadmap".to_string())
+        });
+
+        assert_eq!(
+            rewritten.as_deref(),
+            Some("[[Roadmap]], [[Roadmap|the plan]], [[Roadmap#Goals]], [[Other]]")
+        );
+        assert_eq!(rewrite_links("[[Other]]", |_| None), None);
+    }
+}
@@ -0,0 +1,5 @@
+pub mod atomic;
+pub mod front_matter;
+pub mod links;
+pub mod paths;
+pub mod tags;
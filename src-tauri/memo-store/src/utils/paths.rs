@@ -0,0 +1,253 @@
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+use crate::error::MemoError;
+
+/// Ensure a directory exists, creating it if necessary
+pub fn ensure_dir_exists(path: &PathBuf) -> Result<(), MemoError> {
+    if !path.exists() {
+        fs::create_dir_all(path).map_err(|e| MemoError::io("Failed to create directory", path, e))?;
+    }
+    Ok(())
+}
+
+/// Check whether a path looks like a memo (.md or .txt file, or an encrypted .enc one)
+pub fn is_memo_file(path: &Path) -> bool {
+    let ext = path.extension().and_then(|s| s.to_str());
+    ext == Some("md") || ext == Some("txt") || ext == Some("enc")
+}
+
+/// Check the extension of a memo about to be created or imported. Only plain
+/// .md and .txt memos are made this way; .enc ones come from encrypting them.
+pub fn check_memo_extension(ext: &str) -> Result<(), MemoError> {
+    if ext == "md" || ext == "txt" {
+        Ok(())
+    } else {
+        Err(MemoError::invalid_input(format!("Unsupported file type: .{}", ext)))
+    }
+}
+
+/// Check whether a memo is stored encrypted; its name then says nothing about its title
+pub fn is_encrypted_memo(filename: &str) -> bool {
+    filename.ends_with(".enc")
+}
+
+/// Find memo files below `dir`, descending into folders but skipping hidden
+/// ones such as `.trash` and `.history`
+pub fn walk_memo_files(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+
+    while let Some(current) = pending.pop() {
+        for entry in fs::read_dir(&current)? {
+            let entry = entry?;
+            if entry.file_name().to_string_lossy().starts_with('.') {
+                continue;
+            }
+
+            let path = entry.path();
+            if entry.file_type()?.is_dir() {
+                pending.push(path);
+            } else if is_memo_file(&path) {
+                files.push(path);
+            }
+        }
+    }
+
+    Ok(files)
+}
+
+/// A memo's path relative to the memo directory, e.g. `work/plan.md`, checked
+/// so that joining it onto the memo directory can't end up outside of it or in
+/// its metadata folders. Filenames coming from callers go through this type.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MemoPath(String);
+
+impl MemoPath {
+    /// Accept relative `/`-separated paths to memo files. Absolute
+    /// paths, drive prefixes, backslashes, `.`/`..` and hidden components are
+    /// rejected.
+    pub fn parse(filename: &str) -> Result<MemoPath, MemoError> {
+        let invalid = || MemoError::invalid_input(format!("Invalid memo path: '{}'", filename));
+
+        if filename.contains(['\\', ':', '\0']) {
+            return Err(invalid());
+        }
+        // An empty part means a leading `/` or `//`; `.`, `..` and hidden
+        // folders such as `.trash` all start with a dot
+        if filename.split('/').any(|part| part.is_empty() || part.starts_with('.')) {
+            return Err(invalid());
+        }
+        if !is_memo_file(Path::new(filename)) {
+            return Err(invalid());
+        }
+
+        Ok(MemoPath(filename.to_string()))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl std::ops::Deref for MemoPath {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl AsRef<Path> for MemoPath {
+    fn as_ref(&self) -> &Path {
+        Path::new(&self.0)
+    }
+}
+
+impl std::fmt::Display for MemoPath {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Path of `path` relative to the memo directory with `/` separators, which is
+/// how memos are identified in commands and metadata files. Returns None for
+/// paths outside the memo directory or inside hidden folders.
+pub fn relative_memo_path(memo_dir: &Path, path: &Path) -> Option<String> {
+    let parts = path
+        .strip_prefix(memo_dir)
+        .ok()?
+        .components()
+        .map(|component| match component {
+            Component::Normal(part) => part.to_str(),
+            _ => None,
+        })
+        .collect::<Option<Vec<_>>>()?;
+
+    if parts.is_empty() || parts.iter().any(|part| part.starts_with('.')) {
+        return None;
+    }
+    Some(parts.join("/"))
+}
+
+/// Folder part of a memo's relative path, "" for memos at the top level
+pub fn memo_folder(filename: &str) -> &str {
+    filename.rsplit_once('/').map(|(folder, _)| folder).unwrap_or("")
+}
+
+/// Join a folder and a file name into a memo's relative path
+pub fn join_memo_path(folder: &str, name: &str) -> String {
+    if folder.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}/{}", folder, name)
+    }
+}
+
+/// Title shown for a memo: its file name without folder or extension
+pub fn memo_title(filename: &str) -> &str {
+    let name = filename.rsplit('/').next().unwrap_or(filename);
+    name.trim_end_matches(".md").trim_end_matches(".txt")
+}
+
+/// Clean up a folder path from the UI (`/work//notes/` -> `work/notes`).
+/// Hidden and `..` components are rejected so folders stay inside the memo
+/// directory and out of its metadata folders.
+pub fn normalize_folder(folder: &str) -> Result<String, MemoError> {
+    let mut parts = Vec::new();
+
+    for part in folder.split(['/', '\\']) {
+        let part = part.trim();
+        if part.is_empty() {
+            continue;
+        }
+        if part.starts_with('.') || sanitize_filename(part) != part {
+            return Err(MemoError::invalid_input(format!("Invalid folder name: '{}'", folder)));
+        }
+        parts.push(part);
+    }
+
+    Ok(parts.join("/"))
+}
+
+/// Sanitize a filename by removing invalid characters. Leading dots go too:
+/// hidden files aren't listed and aren't accepted as memo paths.
+pub fn sanitize_filename(title: &str) -> String {
+    let sanitized: String = title
+        .chars()
+        .filter(|c| !matches!(c, '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|'))
+        .collect();
+
+    let trimmed = sanitized.trim().trim_start_matches('.').trim_start();
+    if trimmed.is_empty() {
+        format!("無題のメモ_{}", chrono::Local::now().timestamp())
+    } else {
+        trimmed.chars().take(200).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sanitize_filename() {
+        assert_eq!(sanitize_filename("test/file"), "testfile");
+        assert_eq!(sanitize_filename("test:file"), "testfile");
+        assert_eq!(sanitize_filename("  test  "), "test");
+        assert_eq!(sanitize_filename(".env notes"), "env notes");
+        assert!(sanitize_filename("...").starts_with("無題のメモ_"));
+    }
+
+    #[test]
+    fn test_memo_paths() {
+        let memo_dir = Path::new("/memos");
+        assert_eq!(relative_memo_path(memo_dir, &memo_dir.join("work").join("plan.md")).as_deref(), Some("work/plan.md"));
+        assert_eq!(relative_memo_path(memo_dir, &memo_dir.join(".trash").join("plan.md")), None);
+        assert_eq!(memo_folder("work/notes/plan.md"), "work/notes");
+        assert_eq!(memo_folder("plan.md"), "");
+        assert_eq!(memo_title("work/plan.md"), "plan");
+
+        assert_eq!(normalize_folder("/work//notes/").unwrap(), "work/notes");
+        assert!(normalize_folder("work/../..").is_err());
+        assert!(normalize_folder(".history").is_err());
+    }
+
+    #[test]
+    fn test_memo_path_accepts_memos_in_folders() {
+        assert_eq!(MemoPath::parse("plan.md").unwrap().as_str(), "plan.md");
+        assert_eq!(MemoPath::parse("work/notes/plan.txt").unwrap().as_str(), "work/notes/plan.txt");
+        assert_eq!(MemoPath::parse("日本語 メモ.md").unwrap().as_str(), "日本語 メモ.md");
+    }
+
+    #[test]
+    fn test_memo_path_rejects_traversal() {
+        for attack in [
+            "../../.ssh/id_rsa",
+            "../secret.md",
+            "work/../../secret.md",
+            "./plan.md",
+            "/etc/passwd.md",
+            "//server/share/plan.md",
+            "C:/Windows/win.md",
+            "..\\..\\secret.md",
+            "work\\plan.md",
+            ".trash/20240101_plan.md",
+            ".history/plan.md/versions.json",
+            "work//plan.md",
+            "plan.md\0.txt",
+            "",
+        ] {
+            assert!(MemoPath::parse(attack).is_err(), "accepted {:?}", attack);
+        }
+    }
+
+    #[test]
+    fn test_memo_path_rejects_other_files() {
+        assert!(MemoPath::parse(".pins.json").is_err());
+        assert!(MemoPath::parse("work/script.sh").is_err());
+        assert!(MemoPath::parse("work").is_err());
+        assert!(MemoPath::parse("plan.md/").is_err());
+    }
+}
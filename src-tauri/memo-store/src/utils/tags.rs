@@ -0,0 +1,111 @@
+use crate::utils::front_matter::FrontMatter;
+
+/// Collect the tags of a memo: those listed in its front matter followed by
+/// inline `#tags` in the body. Tags are compared case-insensitively and the
+/// first spelling seen is kept.
+pub fn extract_tags(front_matter: Option<&FrontMatter>, body: &str) -> Vec<String> {
+    let front_matter_tags = front_matter
+        .map(|f| f.tags.iter().map(|tag| normalize_tag(tag)).collect::<Vec<_>>())
+        .unwrap_or_default();
+
+    let mut tags: Vec<String> = Vec::new();
+    for tag in front_matter_tags.into_iter().chain(inline_tags(body)) {
+        if !tag.is_empty() && !tags.iter().any(|t| t.to_lowercase() == tag.to_lowercase()) {
+            tags.push(tag);
+        }
+    }
+    tags
+}
+
+/// Strip the `#` and stray slashes from a tag written in front matter
+fn normalize_tag(tag: &str) -> String {
+    tag.trim().trim_start_matches('#').trim_matches('/').to_string()
+}
+
+fn is_tag_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '_' | '-' | '/')
+}
+
+/// Find `#tag` and `#parent/child` in Markdown text, skipping code blocks,
+/// inline code, headings (`# Title`) and anchors such as `page#section`
+fn inline_tags(body: &str) -> Vec<String> {
+    let mut tags = Vec::new();
+    let mut in_code_block = false;
+
+    for line in body.lines() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_code_block = !in_code_block;
+            continue;
+        }
+        if in_code_block {
+            continue;
+        }
+
+        let mut in_code_span = false;
+        let mut previous: Option<char> = None;
+        for (index, c) in line.char_indices() {
+            if c == '`' {
+                in_code_span = !in_code_span;
+            } else if c == '#' && !in_code_span && previous.map_or(true, char::is_whitespace) {
+                let rest = &line[index + 1..];
+                let end = rest.find(|c: char| !is_tag_char(c)).unwrap_or(rest.len());
+                let tag = rest[..end].trim_end_matches('/');
+
+                // Pure numbers are issue references like #42, not tags
+                if tag.chars().any(|c| c.is_alphabetic() || c == '_') {
+                    tags.push(tag.to_string());
+                }
+            }
+            previous = Some(c);
+        }
+    }
+
+    tags
+}
+
+/// Check whether `tag` is `filter` or nested below it, so `work` matches `work/project-x`
+pub fn tag_matches(tag: &str, filter: &str) -> bool {
+    let tag = tag.to_lowercase();
+    let filter = normalize_tag(filter).to_lowercase();
+
+    tag == filter || tag.strip_prefix(&filter).map_or(false, |rest| rest.starts_with('/'))
+}
+
+/// A tag and the parents it is nested under: `a/b/c` -> `a`, `a/b`, `a/b/c`
+pub fn tag_with_ancestors(tag: &str) -> Vec<&str> {
+    tag.match_indices('/')
+        .map(|(index, _)| &tag[..index])
+        .chain(std::iter::once(tag))
+        .filter(|t| !t.is_empty())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_inline_tags() {
+        let body = "# Heading\nSee #work/project-x and #日本語, not page#anchor or #42.\n\
+                    `#code` stays out\n```\n#fenced\n```\n#Work again\n";
+        assert_eq!(extract_tags(None, body), vec!["work/project-x", "日本語", "Work"]);
+    }
+
+    #[test]
+    fn test_front_matter_tags_come_first_and_dedupe() {
+        let front_matter = FrontMatter {
+            tags: vec!["#Review".to_string(), "work".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(extract_tags(Some(&front_matter), "#review #idea"), vec!["Review", "work", "idea"]);
+    }
+
+    #[test]
+    fn test_hierarchical_tags() {
+        assert!(tag_matches("work/project-x", "work"));
+        assert!(tag_matches("Work", "#work"));
+        assert!(!tag_matches("workshop", "work"));
+        assert_eq!(tag_with_ancestors("a/b/c"), vec!["a", "a/b", "a/b/c"]);
+    }
+}
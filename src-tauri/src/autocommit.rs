@@ -0,0 +1,89 @@
+//! Debounced git commits for vaults with `gitAutoCommit` turned on. Every
+//! write of the memo store schedules a commit; it happens once the vault has
+//! been quiet for a while, so a typing session becomes one commit instead of
+//! one per auto-save.
+
+use memo_store::MemoStore;
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, Once};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::commands::config::get_config;
+
+/// How long after the last write pending changes are committed
+const AUTO_COMMIT_DELAY: Duration = Duration::from_secs(10);
+
+/// How often the worker checks whether the delay has passed
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// When the next commit of each vault with pending changes is due, by the
+/// vault's directory
+static DUE: Lazy<Mutex<HashMap<PathBuf, Instant>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+static WORKER: Once = Once::new();
+
+/// Push the next auto-commit of the vault at `root` back to
+/// `AUTO_COMMIT_DELAY` from now, starting the worker thread on first use
+pub fn schedule(root: &Path) {
+    DUE.lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(root.to_path_buf(), Instant::now() + AUTO_COMMIT_DELAY);
+    WORKER.call_once(|| {
+        thread::spawn(run);
+    });
+}
+
+/// Commit the pending changes of every vault right away, for callers that
+/// exit before the worker would get to them, like the CLI
+pub fn flush() {
+    let pending: Vec<PathBuf> = DUE.lock().unwrap_or_else(|e| e.into_inner()).drain().map(|(root, _)| root).collect();
+    commit_all(pending);
+}
+
+/// Take the vaults whose commit is due
+fn take_due() -> Vec<PathBuf> {
+    let now = Instant::now();
+    let mut due = DUE.lock().unwrap_or_else(|e| e.into_inner());
+    let roots: Vec<PathBuf> = due.iter().filter(|(_, at)| **at <= now).map(|(root, _)| root.clone()).collect();
+    for root in &roots {
+        due.remove(root);
+    }
+    roots
+}
+
+fn run() {
+    loop {
+        thread::sleep(POLL_INTERVAL);
+        commit_all(take_due());
+    }
+}
+
+fn commit_all(roots: Vec<PathBuf>) {
+    for root in roots {
+        if let Err(e) = commit_vault(&root) {
+            eprintln!("Failed to auto-commit memos: {}", e);
+        }
+    }
+}
+
+/// Commit pending changes of the vault at `root` if it has auto-commit
+/// turned on, whether or not it is still the active one
+fn commit_vault(root: &Path) -> Result<(), String> {
+    let config = get_config()?;
+    let enabled = config
+        .vaults
+        .iter()
+        .any(|vault| vault.git_auto_commit && Path::new(&vault.path) == root);
+    if !enabled {
+        return Ok(());
+    }
+
+    let store = MemoStore::open(root)?;
+    if store.is_git_repository() {
+        store.git_commit_changes()?;
+    }
+    Ok(())
+}
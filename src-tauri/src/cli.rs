@@ -0,0 +1,297 @@
+//! Headless subcommands of the app binary for scripts and cron jobs, e.g.
+//! `RakuMemo append Log.md "deployed v2"` or `RakuMemo search budget`.
+//! They go through the same command functions as the GUI, so memos written
+//! here get the same naming, history and search index updates.
+
+use serde::Serialize;
+use std::io::Read;
+use memo_store::utils::paths::{is_encrypted_memo, normalize_folder};
+use memo_store::ConflictPolicy;
+
+use crate::autocommit;
+use crate::commands::archive::{export_vault_archive, import_vault_archive};
+use crate::commands::export::{export_memo, export_site};
+use crate::commands::memo::{
+    create_memo, delete_memo, list_memo_summaries, read_memo, save_memo, search_memos, toggle_pin,
+};
+use crate::commands::sync::sync_vault;
+
+const USAGE: &str = "Usage: RakuMemo <command> [arguments]
+
+Commands:
+  list [--folder FOLDER]              List memos without their content
+  cat <filename> [--plain]            Show a memo; --plain prints only its text
+  create <title> [TEXT|-] [--folder FOLDER] [--ext md|txt]
+                                      Create a memo, reading TEXT from stdin for -
+  append <filename> [TEXT|-]          Append a line to a memo, from stdin for -
+  search <query> [--limit N]          Search memo contents
+  pin <filename>                      Pin a memo
+  unpin <filename>                    Unpin a memo
+  delete <filename>                   Move a memo to the trash
+  export <filename> <output.html>     Write a memo as a standalone HTML document
+  export-site <directory> [--folder FOLDER] [--tag TAG]
+                                      Export memos to a static site with an index
+  backup <output.zip>                 Pack the vault with its metadata into a zip
+  restore <archive.zip> [--into DIR] [--on-conflict keep-both|skip|overwrite]
+                                      Restore a backup into the vault or DIR
+  sync [DIR]                          Sync the vault with DIR, or the directory of
+                                      the last sync
+  help                                Show this message
+
+Output is JSON unless noted otherwise.";
+
+const COMMANDS: &[&str] = &[
+    "list", "cat", "create", "append", "search", "pin", "unpin", "delete", "export", "export-site", "backup",
+    "restore", "sync", "help", "--help",
+];
+
+/// Options that take a value; every other `--option` is a flag
+const VALUE_OPTIONS: &[&str] = &["--folder", "--ext", "--limit", "--tag", "--into", "--on-conflict"];
+
+enum Output {
+    Json(serde_json::Value),
+    Text(String),
+}
+
+/// The CLI never unlocks the vault, so the text of encrypted memos is out of reach
+fn reject_encrypted(filename: &str) -> Result<(), String> {
+    if is_encrypted_memo(filename) {
+        return Err(format!("{} is encrypted; open it in the app to read or change it", filename));
+    }
+    Ok(())
+}
+
+fn json(value: impl Serialize) -> Result<Output, String> {
+    serde_json::to_value(value)
+        .map(Output::Json)
+        .map_err(|e| format!("Failed to serialize output: {}", e))
+}
+
+/// Command line arguments split into positional ones and `--options`
+struct Args {
+    positional: Vec<String>,
+    options: Vec<(String, Option<String>)>,
+}
+
+impl Args {
+    fn parse(args: &[String]) -> Result<Args, String> {
+        let mut positional = Vec::new();
+        let mut options = Vec::new();
+        let mut iter = args.iter();
+
+        while let Some(arg) = iter.next() {
+            if arg.starts_with("--") {
+                let value = if VALUE_OPTIONS.contains(&arg.as_str()) {
+                    Some(iter.next().cloned().ok_or_else(|| format!("{} needs a value", arg))?)
+                } else {
+                    None
+                };
+                options.push((arg.clone(), value));
+            } else {
+                positional.push(arg.clone());
+            }
+        }
+
+        Ok(Args { positional, options })
+    }
+
+    fn required(&self, index: usize, name: &str) -> Result<&str, String> {
+        self.positional
+            .get(index)
+            .map(String::as_str)
+            .ok_or_else(|| format!("Missing <{}>\n\n{}", name, USAGE))
+    }
+
+    fn value(&self, option: &str) -> Option<&str> {
+        self.options
+            .iter()
+            .find(|(name, _)| name == option)
+            .and_then(|(_, value)| value.as_deref())
+    }
+
+    fn flag(&self, option: &str) -> bool {
+        self.options.iter().any(|(name, _)| name == option)
+    }
+
+    /// Text given after the first `skip` arguments; `-` reads it from stdin
+    fn text(&self, skip: usize) -> Result<String, String> {
+        let words = self.positional.get(skip..).unwrap_or_default();
+        if words.len() == 1 && words[0] == "-" {
+            let mut text = String::new();
+            std::io::stdin()
+                .read_to_string(&mut text)
+                .map_err(|e| format!("Failed to read stdin: {}", e))?;
+            return Ok(text);
+        }
+        Ok(words.join(" "))
+    }
+}
+
+/// Run the subcommand in `args` (without the program name) and return the
+/// process exit code, or None when `args` is not a CLI invocation and the
+/// GUI should start instead
+pub fn run(args: &[String]) -> Option<i32> {
+    let (command, rest) = args.split_first()?;
+    if !COMMANDS.contains(&command.as_str()) {
+        return None;
+    }
+
+    let result = Args::parse(rest).and_then(|args| execute(command, &args));
+    // The worker thread would not outlive the process
+    autocommit::flush();
+    Some(match result {
+        Ok(Output::Json(value)) => {
+            println!("{}", serde_json::to_string_pretty(&value).unwrap_or_default());
+            0
+        }
+        Ok(Output::Text(text)) => {
+            print!("{}", text);
+            0
+        }
+        Err(e) => {
+            eprintln!("error: {}", e);
+            1
+        }
+    })
+}
+
+fn execute(command: &str, args: &Args) -> Result<Output, String> {
+    match command {
+        "list" => {
+            let mut items = list_memo_summaries(None, None)?.items;
+            if let Some(folder) = args.value("--folder") {
+                let folder = normalize_folder(folder)?;
+                items.retain(|memo| memo.folder == folder);
+            }
+            json(items)
+        }
+        "cat" => {
+            let filename = args.required(0, "filename")?;
+            if args.flag("--plain") {
+                reject_encrypted(filename)?;
+                Ok(Output::Text(read_memo(filename.to_string(), Some(true))?.content))
+            } else {
+                json(read_memo(filename.to_string(), None)?)
+            }
+        }
+        "create" => {
+            let title = args.required(0, "title")?.to_string();
+            let content = args.text(1)?;
+            let created = create_memo(
+                args.value("--ext").map(str::to_string),
+                args.value("--folder").map(str::to_string),
+            )?;
+            let filename = save_memo(title, content, Some(created.filename), None, None, None)?.filename;
+            json(read_memo(filename, None)?)
+        }
+        "append" => {
+            let filename = args.required(0, "filename")?.to_string();
+            reject_encrypted(&filename)?;
+            let text = args.text(1)?;
+
+            let memo = read_memo(filename.clone(), Some(true))?;
+            let mut content = memo.content;
+            if !content.is_empty() && !content.ends_with('\n') {
+                content.push('\n');
+            }
+            content.push_str(&text);
+            if !content.ends_with('\n') {
+                content.push('\n');
+            }
+
+            // Fail rather than drop a line written by someone else in the meantime
+            let filename = save_memo(memo.title, content, Some(filename), None, None, Some(memo.version))?.filename;
+            json(read_memo(filename, None)?)
+        }
+        "search" => {
+            let query = args.positional.join(" ");
+            if query.trim().is_empty() {
+                return Err(format!("Missing <query>\n\n{}", USAGE));
+            }
+            let limit = match args.value("--limit") {
+                Some(limit) => Some(limit.parse().map_err(|_| format!("Invalid --limit: {}", limit))?),
+                None => None,
+            };
+            json(search_memos(query, limit)?)
+        }
+        "pin" | "unpin" => {
+            let filename = args.required(0, "filename")?.to_string();
+            let wanted = command == "pin";
+
+            let mut pinned = read_memo(filename.clone(), None)?.pinned;
+            if pinned != wanted {
+                pinned = toggle_pin(filename.clone())?.pinned;
+            }
+            json(serde_json::json!({ "filename": filename, "pinned": pinned }))
+        }
+        "delete" => {
+            let filename = args.required(0, "filename")?.to_string();
+            read_memo(filename.clone(), None)?;
+            delete_memo(filename.clone())?;
+            json(serde_json::json!({ "filename": filename, "deleted": true }))
+        }
+        "export" => {
+            let filename = args.required(0, "filename")?.to_string();
+            let target = args.required(1, "output.html")?.to_string();
+            export_memo(filename.clone(), target.clone())?;
+            json(serde_json::json!({ "filename": filename, "exported": target }))
+        }
+        "export-site" => {
+            let directory = args.required(0, "directory")?.to_string();
+            json(export_site(
+                directory,
+                args.value("--folder").map(str::to_string),
+                args.value("--tag").map(str::to_string),
+            )?)
+        }
+        "backup" => {
+            let target = args.required(0, "output.zip")?.to_string();
+            json(export_vault_archive(target)?)
+        }
+        "restore" => {
+            let source = args.required(0, "archive.zip")?.to_string();
+            let on_conflict = match args.value("--on-conflict") {
+                None | Some("keep-both") => ConflictPolicy::KeepBoth,
+                Some("skip") => ConflictPolicy::Skip,
+                Some("overwrite") => ConflictPolicy::Overwrite,
+                Some(other) => return Err(format!("Invalid --on-conflict: {}", other)),
+            };
+            json(import_vault_archive(source, args.value("--into").map(str::to_string), Some(on_conflict))?)
+        }
+        "sync" => json(sync_vault(args.positional.first().cloned())?),
+        _ => Ok(Output::Text(format!("{}\n", USAGE))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let args = Args::parse(&strings(&["Title", "some", "text", "--folder", "work", "--plain"])).unwrap();
+
+        assert_eq!(args.required(0, "title").unwrap(), "Title");
+        assert_eq!(args.text(1).unwrap(), "some text");
+        assert_eq!(args.value("--folder"), Some("work"));
+        assert!(args.flag("--plain"));
+        assert!(Args::parse(&strings(&["--limit"])).is_err());
+    }
+
+    #[test]
+    fn test_encrypted_memos_are_rejected() {
+        let args = Args::parse(&strings(&["a1b2c3.enc", "text"])).unwrap();
+        let error = execute("append", &args).err().unwrap();
+        assert!(error.contains("a1b2c3.enc is encrypted"));
+    }
+
+    #[test]
+    fn test_gui_arguments_are_not_commands() {
+        assert_eq!(run(&[]), None);
+        assert_eq!(run(&strings(&["-psn_0_12345"])), None);
+    }
+}
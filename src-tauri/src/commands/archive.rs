@@ -0,0 +1,25 @@
+use memo_store::{ArchiveManifest, ArchiveRestore, ConflictPolicy, MemoError, MemoStore};
+use std::path::Path;
+
+use crate::commands::memo::memo_store;
+
+/// Pack the active vault, with pins, order, trash and history, into a zip at `target_path`
+#[tauri::command]
+pub fn export_vault_archive(target_path: String) -> Result<ArchiveManifest, MemoError> {
+    memo_store()?.export_archive(Path::new(&target_path))
+}
+
+/// Restore a vault archive into `target_directory`, or into the active vault
+/// when it is omitted. Conflicting files are kept side by side by default.
+#[tauri::command]
+pub fn import_vault_archive(
+    source_path: String,
+    target_directory: Option<String>,
+    on_conflict: Option<ConflictPolicy>,
+) -> Result<ArchiveRestore, MemoError> {
+    let store = match target_directory {
+        Some(directory) => MemoStore::open(directory)?,
+        None => memo_store()?,
+    };
+    store.import_archive(Path::new(&source_path), on_conflict.unwrap_or_default())
+}
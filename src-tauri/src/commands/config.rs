@@ -1,29 +1,127 @@
+use memo_store::utils::atomic::atomic_write;
+use memo_store::utils::paths::ensure_dir_exists;
+use memo_store::MemoError;
 use serde::{Deserialize, Serialize};
 use std::fs;
+use std::path::{Path, PathBuf};
 
-use crate::utils::paths::{ensure_dir_exists, get_config_dir, get_config_file, get_home_dir};
+use crate::utils::paths::{get_config_dir, get_config_file, get_home_dir};
+use crate::watcher;
+
+/// Name given to the memo directory of configs written before vaults existed
+const DEFAULT_VAULT_NAME: &str = "Default";
+
+/// A named memo directory the app can switch between
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Vault {
+    pub name: String,
+    pub path: String,
+    /// Commit changes to the vault's git repository shortly after saves
+    #[serde(default)]
+    pub git_auto_commit: bool,
+    /// Second directory, e.g. on a USB drive or NAS, that `sync_vault` reconciles the vault with
+    #[serde(default)]
+    pub sync_directory: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VaultInfo {
+    pub name: String,
+    pub path: String,
+    pub active: bool,
+    pub git_auto_commit: bool,
+    pub sync_directory: Option<String>,
+}
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct AppConfig {
+    /// Directory of the active vault
     pub memo_directory: String,
     pub auto_save_delay: u32,
+    /// Days a deleted memo stays in the trash; 0 keeps it until purged by hand
+    #[serde(default = "default_trash_retention_days")]
+    pub trash_retention_days: u32,
+    #[serde(default)]
+    pub vaults: Vec<Vault>,
+    #[serde(default)]
+    pub active_vault: Option<String>,
+}
+
+fn default_trash_retention_days() -> u32 {
+    30
+}
+
+impl AppConfig {
+    /// Turn the single `memo_directory` of older configs into a default vault
+    /// and make sure the active vault exists
+    fn ensure_vaults(&mut self) {
+        if self.vaults.is_empty() {
+            self.vaults.push(Vault {
+                name: DEFAULT_VAULT_NAME.to_string(),
+                path: self.memo_directory.clone(),
+                git_auto_commit: false,
+                sync_directory: None,
+            });
+        }
+
+        let active_exists = self
+            .active_vault
+            .as_ref()
+            .map(|name| self.vaults.iter().any(|v| &v.name == name))
+            .unwrap_or(false);
+        if !active_exists {
+            self.active_vault = Some(self.vaults[0].name.clone());
+        }
+    }
+
+    fn find_vault(&self, name: &str) -> Option<&Vault> {
+        self.vaults.iter().find(|v| v.name.eq_ignore_ascii_case(name))
+    }
+
+    /// The vault memos are read from and written to
+    pub fn current_vault(&self) -> Option<&Vault> {
+        self.active_vault.as_deref().and_then(|name| self.find_vault(name))
+    }
+
+    /// Directory memos are read from and written to, i.e. the active vault's
+    pub fn active_memo_directory(&self) -> &str {
+        self.current_vault()
+            .map(|vault| vault.path.as_str())
+            .unwrap_or(&self.memo_directory)
+    }
+
+    /// Point the active vault at another directory
+    fn set_memo_directory(&mut self, memo_directory: &str) {
+        let active = self.active_vault.clone();
+        if let Some(vault) = self.vaults.iter_mut().find(|v| Some(&v.name) == active.as_ref()) {
+            vault.path = memo_directory.to_string();
+        }
+        self.memo_directory = memo_directory.to_string();
+    }
 }
 
 impl Default for AppConfig {
     fn default() -> Self {
-        let home = get_home_dir().unwrap_or_else(|_| std::path::PathBuf::from("."));
+        let home = get_home_dir().unwrap_or_else(|_| PathBuf::from("."));
         let default_memo_dir = home.join("Documents").join("Memos");
 
-        AppConfig {
+        let mut config = AppConfig {
             memo_directory: default_memo_dir.to_string_lossy().to_string(),
             auto_save_delay: 1000,
-        }
+            trash_retention_days: default_trash_retention_days(),
+            vaults: Vec::new(),
+            active_vault: None,
+        };
+        config.ensure_vaults();
+        config
     }
 }
 
 #[tauri::command]
-pub fn get_config() -> Result<AppConfig, String> {
+pub fn get_config() -> Result<AppConfig, MemoError> {
     let config_file = get_config_file()?;
 
     if !config_file.exists() {
@@ -32,31 +130,49 @@ pub fn get_config() -> Result<AppConfig, String> {
     }
 
     let content = fs::read_to_string(&config_file)
-        .map_err(|e| format!("Failed to read config file: {}", e))?;
+        .map_err(|e| MemoError::io("Failed to read config file", &config_file, e))?;
 
-    let config: AppConfig = serde_json::from_str(&content)
-        .map_err(|e| format!("Failed to parse config: {}", e))?;
+    let mut config: AppConfig = serde_json::from_str(&content)
+        .map_err(|e| MemoError::format("Failed to parse config", Some(&config_file), e))?;
+    config.ensure_vaults();
 
     Ok(config)
 }
 
 #[tauri::command]
-pub fn save_config(config: AppConfig) -> Result<(), String> {
+pub fn save_config(mut config: AppConfig) -> Result<(), MemoError> {
+    // Callers that don't know about vaults send none; keep the stored ones
+    if config.vaults.is_empty() {
+        let stored = get_config()?;
+        config.vaults = stored.vaults;
+        config.active_vault = stored.active_vault;
+    }
+    config.ensure_vaults();
+
+    // memoDirectory is the active vault's directory, so changing it (e.g. from
+    // the settings screen) points that vault somewhere else
+    let memo_directory = config.memo_directory.clone();
+    config.set_memo_directory(&memo_directory);
+
     let config_dir = get_config_dir()?;
     ensure_dir_exists(&config_dir)?;
 
     let config_file = get_config_file()?;
     let content = serde_json::to_string_pretty(&config)
-        .map_err(|e| format!("Failed to serialize config: {}", e))?;
+        .map_err(|e| MemoError::format("Failed to serialize config", None, e))?;
+
+    atomic_write(&config_file, content)
+        .map_err(|e| MemoError::io("Failed to write config file", &config_file, e))?;
 
-    fs::write(&config_file, content)
-        .map_err(|e| format!("Failed to write config file: {}", e))?;
+    // Follow a changed memoDirectory; the config is saved either way and the
+    // UI can still reload manually if watching fails
+    let _ = watcher::refresh();
 
     Ok(())
 }
 
 #[tauri::command]
-pub fn update_config(partial_config: serde_json::Value) -> Result<AppConfig, String> {
+pub fn update_config(partial_config: serde_json::Value) -> Result<AppConfig, MemoError> {
     let mut config = get_config()?;
 
     // Merge partial config
@@ -66,7 +182,137 @@ pub fn update_config(partial_config: serde_json::Value) -> Result<AppConfig, Str
     if let Some(auto_save_delay) = partial_config.get("autoSaveDelay").and_then(|v| v.as_u64()) {
         config.auto_save_delay = auto_save_delay as u32;
     }
+    if let Some(trash_retention_days) = partial_config.get("trashRetentionDays").and_then(|v| v.as_u64()) {
+        config.trash_retention_days = trash_retention_days as u32;
+    }
 
     save_config(config.clone())?;
     Ok(config)
 }
+
+#[tauri::command]
+pub fn list_vaults() -> Result<Vec<VaultInfo>, MemoError> {
+    let config = get_config()?;
+
+    Ok(config
+        .vaults
+        .iter()
+        .map(|vault| VaultInfo {
+            name: vault.name.clone(),
+            path: vault.path.clone(),
+            active: config.active_vault.as_ref() == Some(&vault.name),
+            git_auto_commit: vault.git_auto_commit,
+            sync_directory: vault.sync_directory.clone(),
+        })
+        .collect())
+}
+
+/// Register a directory as a new vault, creating it if needed. The active
+/// vault stays the same until `switch_vault` is called.
+#[tauri::command]
+pub fn add_vault(name: String, path: String) -> Result<Vault, MemoError> {
+    let mut config = get_config()?;
+    let name = name.trim().to_string();
+    let path = path.trim().to_string();
+
+    if name.is_empty() {
+        return Err(MemoError::invalid_input("Vault name is empty".to_string()));
+    }
+    if path.is_empty() {
+        return Err(MemoError::invalid_input("Vault path is empty".to_string()));
+    }
+    if let Some(existing) = config.find_vault(&name) {
+        return Err(MemoError::already_exists(
+            format!("Vault '{}' already exists", name),
+            Path::new(&existing.path),
+        ));
+    }
+    if let Some(existing) = config.vaults.iter().find(|v| v.path == path) {
+        return Err(MemoError::already_exists(
+            format!("'{}' is already the directory of vault '{}'", path, existing.name),
+            Path::new(&path),
+        ));
+    }
+
+    ensure_dir_exists(&PathBuf::from(&path))?;
+
+    let vault = Vault {
+        name,
+        path,
+        git_auto_commit: false,
+        sync_directory: None,
+    };
+    config.vaults.push(vault.clone());
+    save_config(config)?;
+
+    Ok(vault)
+}
+
+/// Make another vault the active one; memo commands use its directory from now on
+#[tauri::command]
+pub fn switch_vault(name: String) -> Result<AppConfig, MemoError> {
+    let mut config = get_config()?;
+    let vault = config
+        .find_vault(&name)
+        .cloned()
+        .ok_or_else(|| MemoError::NotFound {
+            message: format!("Vault '{}' not found", name),
+            path: None,
+        })?;
+
+    ensure_dir_exists(&PathBuf::from(&vault.path))?;
+    config.active_vault = Some(vault.name);
+    config.memo_directory = vault.path;
+
+    save_config(config.clone())?;
+    Ok(config)
+}
+
+/// Change a setting of the active vault and save the config
+fn update_active_vault(update: impl FnOnce(&mut Vault)) -> Result<AppConfig, MemoError> {
+    let mut config = get_config()?;
+    let active = config.active_vault.clone();
+    if let Some(vault) = config.vaults.iter_mut().find(|v| Some(&v.name) == active.as_ref()) {
+        update(vault);
+    }
+
+    save_config(config.clone())?;
+    Ok(config)
+}
+
+/// Turn automatic git commits of the active vault on or off
+pub(crate) fn set_git_auto_commit(enabled: bool) -> Result<AppConfig, MemoError> {
+    update_active_vault(|vault| vault.git_auto_commit = enabled)
+}
+
+/// Remember the directory the active vault syncs with
+pub(crate) fn set_sync_directory(directory: &str) -> Result<AppConfig, MemoError> {
+    update_active_vault(|vault| vault.sync_directory = Some(directory.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_without_vaults_gets_default_vault() {
+        let mut config: AppConfig =
+            serde_json::from_str(r#"{"memoDirectory": "/memos", "autoSaveDelay": 1000}"#).unwrap();
+        config.ensure_vaults();
+
+        assert_eq!(
+            config.vaults,
+            vec![Vault {
+                name: "Default".to_string(),
+                path: "/memos".to_string(),
+                git_auto_commit: false,
+                sync_directory: None,
+            }]
+        );
+        assert_eq!(config.active_vault.as_deref(), Some("Default"));
+        assert_eq!(config.active_memo_directory(), "/memos");
+
+        config.set_memo_directory("/elsewhere");
+        assert_eq!(config.vaults[0].path, "/elsewhere");
+    }
+}
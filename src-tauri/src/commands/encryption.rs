@@ -0,0 +1,35 @@
+use memo_store::{MemoError, MemoPath};
+
+use crate::commands::memo::memo_store;
+
+/// Unlock the encrypted memos of the active vault. The first unlock sets the
+/// passphrase. The derived key is kept in memory only, until `lock_vault`.
+#[tauri::command]
+pub fn unlock_vault(passphrase: String) -> Result<(), MemoError> {
+    memo_store()?.unlock(&passphrase)
+}
+
+#[tauri::command]
+pub fn lock_vault() -> Result<(), MemoError> {
+    memo_store()?.lock();
+    Ok(())
+}
+
+#[tauri::command]
+pub fn is_vault_unlocked() -> Result<bool, MemoError> {
+    Ok(memo_store()?.is_unlocked())
+}
+
+/// Encrypt a memo and return its new filename, which no longer shows the title.
+/// Its history is deleted, but git commits, the sync target and the trash
+/// keep any plain copies made before.
+#[tauri::command]
+pub fn encrypt_memo(filename: String) -> Result<String, MemoError> {
+    memo_store()?.encrypt_memo(&MemoPath::parse(&filename)?)
+}
+
+/// Store an encrypted memo as plain text again and return its new filename
+#[tauri::command]
+pub fn decrypt_memo(filename: String) -> Result<String, MemoError> {
+    memo_store()?.decrypt_memo(&MemoPath::parse(&filename)?)
+}
@@ -0,0 +1,17 @@
+use memo_store::{ExportedSite, MemoError, MemoPath};
+use std::path::Path;
+
+use crate::commands::memo::memo_store;
+
+/// Write a memo as a standalone HTML document, with its styles embedded and
+/// local images inlined, to `target_path`
+#[tauri::command]
+pub fn export_memo(filename: String, target_path: String) -> Result<(), MemoError> {
+    memo_store()?.export_memo(&MemoPath::parse(&filename)?, Path::new(&target_path))
+}
+
+/// Export a folder, a tag or every memo to a static site with an index page
+#[tauri::command]
+pub fn export_site(target_dir: String, folder: Option<String>, tag: Option<String>) -> Result<ExportedSite, MemoError> {
+    memo_store()?.export_site(folder.as_deref(), tag.as_deref(), Path::new(&target_dir))
+}
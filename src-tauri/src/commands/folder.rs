@@ -0,0 +1,21 @@
+use memo_store::MemoError;
+
+use crate::commands::memo::memo_store;
+
+/// List every folder in the memo directory as a relative path, e.g. `work/project-x`
+#[tauri::command]
+pub fn list_folders() -> Result<Vec<String>, MemoError> {
+    memo_store()?.list_folders()
+}
+
+/// Create a folder (and any missing parents) and return its normalized path
+#[tauri::command]
+pub fn create_folder(path: String) -> Result<String, MemoError> {
+    memo_store()?.create_folder(&path)
+}
+
+/// Rename or move a folder, carrying pins, order and history of the memos in it
+#[tauri::command]
+pub fn rename_folder(old_path: String, new_path: String) -> Result<String, MemoError> {
+    memo_store()?.rename_folder(&old_path, &new_path)
+}
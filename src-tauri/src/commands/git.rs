@@ -0,0 +1,42 @@
+use memo_store::{GitCommit, GitPullResult, GitStatus, MemoError, MemoPath};
+
+use crate::commands::config::set_git_auto_commit;
+use crate::commands::memo::memo_store;
+
+/// Number of commits `git_log_for_memo` returns unless asked for more
+const DEFAULT_LOG_LIMIT: usize = 50;
+
+/// Keep the active vault in git: initialize the repository if needed, point
+/// `origin` at `remote` when given and turn auto-commits on or off
+#[tauri::command]
+pub fn enable_git(remote: Option<String>, auto_commit: Option<bool>) -> Result<GitStatus, MemoError> {
+    let store = memo_store()?;
+    store.git_init()?;
+    if let Some(remote) = remote.as_deref().map(str::trim).filter(|remote| !remote.is_empty()) {
+        store.git_set_remote(remote)?;
+    }
+    set_git_auto_commit(auto_commit.unwrap_or(true))?;
+    store.git_status()
+}
+
+#[tauri::command]
+pub fn git_status() -> Result<GitStatus, MemoError> {
+    memo_store()?.git_status()
+}
+
+#[tauri::command]
+pub fn git_log_for_memo(filename: String, limit: Option<usize>) -> Result<Vec<GitCommit>, MemoError> {
+    memo_store()?.git_log_for_memo(&MemoPath::parse(&filename)?, limit.unwrap_or(DEFAULT_LOG_LIMIT))
+}
+
+/// Merge in the remote's changes. Memos changed on both sides keep the local
+/// version and get the remote one as a conflict copy; both are returned.
+#[tauri::command]
+pub fn git_pull() -> Result<GitPullResult, MemoError> {
+    memo_store()?.git_pull()
+}
+
+#[tauri::command]
+pub fn git_push() -> Result<(), MemoError> {
+    memo_store()?.git_push()
+}
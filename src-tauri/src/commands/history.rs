@@ -0,0 +1,21 @@
+use memo_store::{DiffHunk, MemoError, MemoMetadata, MemoPath, MemoVersion};
+
+use crate::commands::memo::memo_store;
+
+#[tauri::command]
+pub fn list_memo_versions(filename: String) -> Result<Vec<MemoVersion>, MemoError> {
+    memo_store()?.list_versions(&MemoPath::parse(&filename)?)
+}
+
+/// Diff two versions of a memo; `to` defaults to the current content
+#[tauri::command]
+pub fn diff_memo_versions(filename: String, from: String, to: Option<String>) -> Result<Vec<DiffHunk>, MemoError> {
+    memo_store()?.diff_versions(&MemoPath::parse(&filename)?, &from, to.as_deref())
+}
+
+/// Replace a memo's content with an earlier version; the current content is
+/// kept as a version of its own first, so restoring can be undone
+#[tauri::command]
+pub fn restore_memo_version(filename: String, version_id: String) -> Result<MemoMetadata, MemoError> {
+    memo_store()?.restore_version(&MemoPath::parse(&filename)?, &version_id)
+}
@@ -0,0 +1,8 @@
+use memo_store::{Backlink, MemoError, MemoPath};
+
+use crate::commands::memo::memo_store;
+
+#[tauri::command]
+pub fn get_backlinks(filename: String) -> Result<Vec<Backlink>, MemoError> {
+    memo_store()?.backlinks(&MemoPath::parse(&filename)?)
+}
@@ -1,2 +1,12 @@
+pub mod archive;
 pub mod config;
+pub mod encryption;
+pub mod export;
+pub mod folder;
+pub mod git;
+pub mod history;
+pub mod links;
 pub mod memo;
+pub mod sync;
+pub mod tags;
+pub mod trash;
@@ -0,0 +1,23 @@
+use memo_store::{MemoError, SyncReport};
+use std::path::Path;
+
+use crate::commands::config::{get_config, set_sync_directory};
+use crate::commands::memo::memo_store;
+
+/// Reconcile the active vault with a second directory. A given `directory`
+/// is remembered for the next sync; without one the remembered one is used.
+#[tauri::command]
+pub fn sync_vault(directory: Option<String>) -> Result<SyncReport, MemoError> {
+    let directory = match directory.map(|d| d.trim().to_string()).filter(|d| !d.is_empty()) {
+        Some(directory) => {
+            set_sync_directory(&directory)?;
+            directory
+        }
+        None => get_config()?
+            .current_vault()
+            .and_then(|vault| vault.sync_directory.clone())
+            .ok_or_else(|| MemoError::invalid_input("No directory to sync with has been chosen".to_string()))?,
+    };
+
+    memo_store()?.sync_with(Path::new(&directory))
+}
@@ -0,0 +1,16 @@
+use memo_store::{MemoError, MemoMetadata, TagCount};
+
+use crate::commands::memo::memo_store;
+
+/// List every tag in use with the number of memos carrying it. Parents of
+/// hierarchical tags are listed too, so `#work/project-x` also counts for `work`.
+#[tauri::command]
+pub fn list_tags() -> Result<Vec<TagCount>, MemoError> {
+    memo_store()?.list_tags()
+}
+
+/// List memos carrying `tag` or a tag nested below it, in sidebar order
+#[tauri::command]
+pub fn list_memos_by_tag(tag: String) -> Result<Vec<MemoMetadata>, MemoError> {
+    memo_store()?.memos_by_tag(&tag)
+}
@@ -0,0 +1,20 @@
+use memo_store::{MemoError, MemoMetadata, TrashEntry};
+
+use crate::commands::memo::memo_store;
+
+#[tauri::command]
+pub fn list_trash() -> Result<Vec<TrashEntry>, MemoError> {
+    memo_store()?.list_trash()
+}
+
+#[tauri::command]
+pub fn restore_memo(id: String) -> Result<MemoMetadata, MemoError> {
+    memo_store()?.restore(&id)
+}
+
+/// Permanently delete one trashed memo, or the whole trash when `id` is omitted.
+/// Returns the number of memos removed.
+#[tauri::command]
+pub fn purge_trash(id: Option<String>) -> Result<usize, MemoError> {
+    memo_store()?.purge_trash(id.as_deref())
+}
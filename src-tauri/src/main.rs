@@ -1,14 +1,33 @@
 // Prevents additional console window on Windows in release, DO NOT REMOVE!!
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+mod autocommit;
+mod cli;
 mod commands;
 mod utils;
+mod watcher;
 
-use commands::config::{get_config, save_config, update_config};
-use commands::memo::{create_memo, delete_memo, list_memos, read_memo, save_memo, toggle_pin, update_memo_order, import_memo_from_dialog, import_memo_from_content};
+use commands::archive::{export_vault_archive, import_vault_archive};
+use commands::config::{add_vault, get_config, list_vaults, save_config, switch_vault, update_config};
+use commands::encryption::{decrypt_memo, encrypt_memo, is_vault_unlocked, lock_vault, unlock_vault};
+use commands::export::{export_memo, export_site};
+use commands::folder::{create_folder, list_folders, rename_folder};
+use commands::git::{enable_git, git_log_for_memo, git_pull, git_push, git_status};
+use commands::history::{diff_memo_versions, list_memo_versions, restore_memo_version};
+use commands::links::get_backlinks;
+use commands::memo::{create_memo, delete_memo, list_memo_summaries, list_memos, merge_memo, move_memo, read_memo, save_memo, search_memos, toggle_pin, update_memo_order, import_memo_from_dialog, import_memo_from_content};
+use commands::sync::sync_vault;
+use commands::tags::{list_memos_by_tag, list_tags};
+use commands::trash::{list_trash, purge_trash, restore_memo};
 use tauri::{CustomMenuItem, Menu, MenuItem, Submenu};
 
 fn main() {
+    // `RakuMemo <command> ...` runs headless instead of opening a window
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Some(code) = cli::run(&args) {
+        std::process::exit(code);
+    }
+
     let file_menu = Submenu::new("File", Menu::new()
         .add_item(CustomMenuItem::new("save", "Save").accelerator("CmdOrCtrl+S")));
 
@@ -33,6 +52,13 @@ fn main() {
 
     tauri::Builder::default()
         .menu(menu)
+        .setup(|app| {
+            // Reloading still works without the watcher, so don't refuse to start
+            if let Err(e) = watcher::start(app.handle()) {
+                eprintln!("Failed to watch memo directory: {}", e);
+            }
+            Ok(())
+        })
         .on_menu_event(|event| {
             let window = event.window();
             match event.menu_item_id() {
@@ -48,16 +74,61 @@ fn main() {
             get_config,
             save_config,
             update_config,
+            // Vault commands
+            list_vaults,
+            add_vault,
+            switch_vault,
             // Memo commands
             list_memos,
+            list_memo_summaries,
+            search_memos,
             read_memo,
             save_memo,
+            merge_memo,
             delete_memo,
             create_memo,
             toggle_pin,
             update_memo_order,
             import_memo_from_dialog,
             import_memo_from_content,
+            move_memo,
+            // Folder commands
+            list_folders,
+            create_folder,
+            rename_folder,
+            // Trash commands
+            list_trash,
+            restore_memo,
+            purge_trash,
+            // History commands
+            list_memo_versions,
+            diff_memo_versions,
+            restore_memo_version,
+            // Tag commands
+            list_tags,
+            list_memos_by_tag,
+            // Link commands
+            get_backlinks,
+            // Export commands
+            export_memo,
+            export_site,
+            // Backup commands
+            export_vault_archive,
+            import_vault_archive,
+            // Sync commands
+            sync_vault,
+            // Git commands
+            enable_git,
+            git_status,
+            git_log_for_memo,
+            git_pull,
+            git_push,
+            // Encryption commands
+            unlock_vault,
+            lock_vault,
+            is_vault_unlocked,
+            encrypt_memo,
+            decrypt_memo,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
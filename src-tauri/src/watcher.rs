@@ -0,0 +1,210 @@
+use notify::event::{EventKind, ModifyKind, RenameMode};
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Manager};
+use memo_store::utils::paths::{is_memo_file, relative_memo_path};
+
+use crate::commands::memo::get_memo_directory;
+
+/// Event emitted to the UI whenever a memo changes on disk
+const MEMO_CHANGED_EVENT: &str = "memo-changed";
+
+/// How long after the app itself touched a file its filesystem events are ignored
+const OWN_WRITE_WINDOW: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ChangeKind {
+    Created,
+    Modified,
+    Deleted,
+    Renamed,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MemoChange {
+    pub kind: ChangeKind,
+    pub filename: String,
+    pub old_filename: Option<String>,
+}
+
+struct WatchState {
+    app: AppHandle,
+    memo_dir: PathBuf,
+    _watcher: RecommendedWatcher,
+}
+
+static STATE: Lazy<Mutex<Option<WatchState>>> = Lazy::new(|| Mutex::new(None));
+
+/// Files recently written or removed by the app, with the time it happened
+static OWN_WRITES: Lazy<Mutex<Option<HashMap<PathBuf, Instant>>>> = Lazy::new(|| Mutex::new(None));
+
+/// Record that the app is about to write, rename or delete `path`, so the
+/// resulting filesystem events are not reported back to the UI
+pub fn mark_own_write(path: &Path) {
+    let mut guard = OWN_WRITES.lock().unwrap_or_else(|e| e.into_inner());
+    let writes = guard.get_or_insert_with(HashMap::new);
+
+    let now = Instant::now();
+    writes.retain(|_, at| now.duration_since(*at) < OWN_WRITE_WINDOW);
+    writes.insert(path.to_path_buf(), now);
+}
+
+fn is_own_write(path: &Path) -> bool {
+    let guard = OWN_WRITES.lock().unwrap_or_else(|e| e.into_inner());
+    guard
+        .as_ref()
+        .and_then(|writes| writes.get(path))
+        .map(|at| at.elapsed() < OWN_WRITE_WINDOW)
+        .unwrap_or(false)
+}
+
+/// Start watching the configured memo directory and forward changes to `app`
+pub fn start(app: AppHandle) -> Result<(), String> {
+    let memo_dir = get_memo_directory()?;
+    let watcher = watch(&app, &memo_dir)?;
+
+    let mut state = STATE.lock().unwrap_or_else(|e| e.into_inner());
+    *state = Some(WatchState {
+        app,
+        memo_dir,
+        _watcher: watcher,
+    });
+    Ok(())
+}
+
+/// Follow the memo directory after the config changed; does nothing if the
+/// watcher was never started or the directory stayed the same
+pub fn refresh() -> Result<(), String> {
+    let mut state = STATE.lock().unwrap_or_else(|e| e.into_inner());
+    let current = match state.as_mut() {
+        Some(current) => current,
+        None => return Ok(()),
+    };
+
+    let memo_dir = get_memo_directory()?;
+    if memo_dir != current.memo_dir {
+        current._watcher = watch(&current.app, &memo_dir)?;
+        current.memo_dir = memo_dir;
+    }
+    Ok(())
+}
+
+fn watch(app: &AppHandle, memo_dir: &Path) -> Result<RecommendedWatcher, String> {
+    let app = app.clone();
+    let dir = memo_dir.to_path_buf();
+    let mut watcher = notify::recommended_watcher(move |result: notify::Result<Event>| {
+        if let Ok(event) = result {
+            for change in changes_from_event(&dir, &event) {
+                let _ = app.emit_all(MEMO_CHANGED_EVENT, change);
+            }
+        }
+    })
+    .map_err(|e| format!("Failed to create file watcher: {}", e))?;
+
+    watcher
+        .watch(memo_dir, RecursiveMode::Recursive)
+        .map_err(|e| format!("Failed to watch memo directory: {}", e))?;
+
+    Ok(watcher)
+}
+
+/// Relative filename of a memo the UI cares about; hidden files such as
+/// `.pins.json`, editor swap files and anything in `.trash` or `.history` are skipped
+fn memo_filename(memo_dir: &Path, path: &Path) -> Option<String> {
+    if !is_memo_file(path) || is_own_write(path) {
+        return None;
+    }
+
+    relative_memo_path(memo_dir, path)
+}
+
+/// Translate a raw filesystem event into the memo changes it represents
+fn changes_from_event(memo_dir: &Path, event: &Event) -> Vec<MemoChange> {
+    let change = |kind, path: &PathBuf| {
+        memo_filename(memo_dir, path).map(|filename| MemoChange {
+            kind,
+            filename,
+            old_filename: None,
+        })
+    };
+
+    match &event.kind {
+        EventKind::Create(_) => event.paths.iter().filter_map(|p| change(ChangeKind::Created, p)).collect(),
+        EventKind::Remove(_) => event.paths.iter().filter_map(|p| change(ChangeKind::Deleted, p)).collect(),
+        EventKind::Modify(ModifyKind::Name(mode)) => match (mode, event.paths.as_slice()) {
+            (RenameMode::Both, [from, to]) => match (memo_filename(memo_dir, from), memo_filename(memo_dir, to)) {
+                (Some(old_filename), Some(filename)) => vec![MemoChange {
+                    kind: ChangeKind::Renamed,
+                    filename,
+                    old_filename: Some(old_filename),
+                }],
+                // Editors that save through a temporary file rename it over the memo
+                (None, Some(_)) => change(ChangeKind::Created, to).into_iter().collect(),
+                (Some(_), None) => change(ChangeKind::Deleted, from).into_iter().collect(),
+                (None, None) => Vec::new(),
+            },
+            (RenameMode::From, paths) => paths.iter().filter_map(|p| change(ChangeKind::Deleted, p)).collect(),
+            (RenameMode::To, paths) => paths.iter().filter_map(|p| change(ChangeKind::Created, p)).collect(),
+            // Some platforms only report that a name changed; check which side we are on
+            (_, paths) => paths
+                .iter()
+                .filter_map(|p| {
+                    let kind = if p.exists() { ChangeKind::Created } else { ChangeKind::Deleted };
+                    change(kind, p)
+                })
+                .collect(),
+        },
+        EventKind::Modify(ModifyKind::Metadata(_)) => Vec::new(),
+        EventKind::Modify(_) => event.paths.iter().filter_map(|p| change(ChangeKind::Modified, p)).collect(),
+        _ => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use notify::event::{CreateKind, DataChange};
+
+    #[test]
+    fn test_changes_from_event() {
+        let dir = PathBuf::from("/memos");
+
+        let renamed = Event::new(EventKind::Modify(ModifyKind::Name(RenameMode::Both)))
+            .add_path(dir.join("old.md"))
+            .add_path(dir.join("new.md"));
+        assert_eq!(
+            changes_from_event(&dir, &renamed),
+            vec![MemoChange {
+                kind: ChangeKind::Renamed,
+                filename: "new.md".to_string(),
+                old_filename: Some("old.md".to_string()),
+            }]
+        );
+
+        let saved_via_temp_file = Event::new(EventKind::Modify(ModifyKind::Name(RenameMode::Both)))
+            .add_path(dir.join(".note.md.swp"))
+            .add_path(dir.join("note.md"));
+        assert_eq!(changes_from_event(&dir, &saved_via_temp_file)[0].kind, ChangeKind::Created);
+
+        let metadata_file = Event::new(EventKind::Create(CreateKind::File)).add_path(dir.join(".pins.json"));
+        assert!(changes_from_event(&dir, &metadata_file).is_empty());
+
+        mark_own_write(&dir.join("mine.md"));
+        let own_write = Event::new(EventKind::Modify(ModifyKind::Data(DataChange::Content)))
+            .add_path(dir.join("mine.md"));
+        assert!(changes_from_event(&dir, &own_write).is_empty());
+
+        let in_folder = Event::new(EventKind::Create(CreateKind::File)).add_path(dir.join("work").join("plan.md"));
+        assert_eq!(changes_from_event(&dir, &in_folder)[0].filename, "work/plan.md");
+
+        let trashed = Event::new(EventKind::Create(CreateKind::File)).add_path(dir.join(".trash").join("plan.md"));
+        assert!(changes_from_event(&dir, &trashed).is_empty());
+    }
+}
@@ -4,7 +4,8 @@ import { Memo } from './types/memo';
 import MainLayout from './components/Layout/MainLayout';
 import { useMemos } from './hooks/useMemos';
 import { useAutoSave } from './hooks/useAutoSave';
-import { saveMemo, togglePin, updateMemoOrder } from './services/fileService';
+import { isConflict, loadMemo, saveMemo, saveMemoMerging, togglePin, updateMemoOrder } from './services/fileService';
+import { CommandError, SavedMemo } from './services/tauriTypes';
 
 function readFileAsText(file: File): Promise<string> {
   return new Promise((resolve, reject) => {
@@ -52,15 +53,43 @@ function App() {
     return () => { unlisten?.(); };
   });
 
+  // 保存結果を現在のメモに反映する。次の保存は新しいバージョンを渡す必要がある
+  // マージされた場合は、送った内容から編集が進んでいなければエディタも更新する
+  const applySaved = (oldFilename: string, sentContent: string, saved: SavedMemo & { content: string }) => {
+    setCurrentMemo((prev) => prev && prev.filename === oldFilename
+      ? { ...prev, filename: saved.filename, version: saved.version, content: saved.content }
+      : prev);
+    if (saved.content !== sentContent) {
+      setEditingContent((content) => content === sentContent ? saved.content : content);
+    }
+  };
+
+  // 他のプログラムによる変更とマージできなかった場合、上書きするか変更後の内容を読み込むか選ぶ
+  const handleConflict = async (memo: Memo, content: string, error: CommandError) => {
+    try {
+      if (confirm('このメモは他のプログラムで変更されています。\n上書きしますか？\n（キャンセルすると変更後の内容を読み込みます）')) {
+        const saved = await saveMemo(memo.title || '無題', content, memo.filename, error.currentVersion ?? undefined);
+        applySaved(memo.filename, content, { ...saved, content });
+      } else {
+        const current = await loadMemo(memo.filename);
+        setCurrentMemo(current);
+        setEditingContent(current?.content ?? '');
+      }
+      await reloadMemos();
+    } catch (saveError) {
+      console.error('保存に失敗しました:', saveError);
+      alert('保存に失敗しました');
+    }
+  };
+
   // 自動保存機能
-  useAutoSave(currentMemo, editingContent, async (newFilename) => {
+  useAutoSave(currentMemo, editingContent, async (saved, oldFilename, sentContent) => {
+    applySaved(oldFilename, sentContent, saved);
     // ファイル名が変更された場合はメモリストを再読み込み
-    await reloadMemos();
-    // 現在のメモのファイル名を更新
-    if (currentMemo) {
-      setCurrentMemo({ ...currentMemo, filename: newFilename });
+    if (saved.filename !== oldFilename) {
+      await reloadMemos();
     }
-  });
+  }, { onConflict: handleConflict });
 
   // 新規メモ作成
   const handleCreateMemo = async (extension: string = 'md') => {
@@ -92,14 +121,13 @@ function App() {
   const handleSave = async () => {
     if (!currentMemo) return;
 
+    const content = editingContent;
     try {
       const title = currentMemo.title || '無題';
-      const newFilename = await saveMemo(title, editingContent, currentMemo.filename);
+      const saved = await saveMemoMerging(title, content, currentMemo.filename, currentMemo.version);
 
-      // ファイル名が変更された場合は更新
-      if (newFilename !== currentMemo.filename) {
-        setCurrentMemo({ ...currentMemo, filename: newFilename });
-      }
+      // ファイル名とバージョンを更新
+      applySaved(currentMemo.filename, content, saved);
 
       // メモリストを再読み込み
       await reloadMemos();
@@ -108,6 +136,10 @@ function App() {
       setSaveMessage(true);
       setTimeout(() => setSaveMessage(false), 2000);
     } catch (error) {
+      if (isConflict(error)) {
+        await handleConflict(currentMemo, content, error);
+        return;
+      }
       console.error('保存に失敗しました:', error);
       alert('保存に失敗しました');
     }
@@ -163,10 +195,14 @@ function App() {
       if (contentChanged || titleChanged) {
         try {
           const title = currentMemo.title || '無題';
-          await saveMemo(title, editingContent, currentMemo.filename);
+          await saveMemoMerging(title, editingContent, currentMemo.filename, currentMemo.version);
           await reloadMemos();
         } catch (error) {
-          console.error('メモの保存に失敗しました:', error);
+          if (isConflict(error)) {
+            await handleConflict(currentMemo, editingContent, error);
+          } else {
+            console.error('メモの保存に失敗しました:', error);
+          }
         }
       }
     }
@@ -179,7 +215,9 @@ function App() {
   // ピン留め切り替え
   const handleTogglePin = async (filename: string) => {
     try {
-      await togglePin(filename);
+      const { version } = await togglePin(filename);
+      // 開いているメモなら、次の保存が競合にならないようバージョンを更新
+      setCurrentMemo((prev) => prev && prev.filename === filename ? { ...prev, version } : prev);
       await reloadMemos();
     } catch (error) {
       console.error('ピン留めの切り替えに失敗しました:', error);
@@ -1,23 +1,29 @@
 import { useEffect, useRef } from 'react';
 import { Memo } from '../types/memo';
-import { saveMemo } from '../services/fileService';
+import { isConflict, saveMemoMerging } from '../services/fileService';
+import { CommandError, SavedMemo } from '../services/tauriTypes';
 
 interface UseAutoSaveOptions {
   delay?: number; // 自動保存の遅延時間（ms）デフォルト: 1000
+  // 他のプログラムによる変更とマージできなかった場合に呼ばれる
+  onConflict?: (memo: Memo, content: string, error: CommandError) => void;
 }
 
 export function useAutoSave(
   currentMemo: Memo | null,
   editingContent: string,
-  onSaved?: (newFilename: string) => void,
+  // 保存のたびに呼ばれる。バージョンは毎回変わり、マージした場合は内容も変わる
+  onSaved?: (saved: SavedMemo & { content: string }, oldFilename: string, sentContent: string) => void,
   options: UseAutoSaveOptions = {}
 ) {
   const { delay = 1000 } = options;
   const timeoutRef = useRef<NodeJS.Timeout | null>(null);
   const previousContentRef = useRef<string>('');
   const onSavedRef = useRef(onSaved);
+  const onConflictRef = useRef(options.onConflict);
   const currentMemoRef = useRef(currentMemo);
   onSavedRef.current = onSaved;
+  onConflictRef.current = options.onConflict;
   currentMemoRef.current = currentMemo;
 
   useEffect(() => {
@@ -42,21 +48,25 @@ export function useAutoSave(
     }
 
     // デバウンス処理で自動保存
-    const memo = currentMemo;
+    const scheduledMemo = currentMemo;
     timeoutRef.current = setTimeout(async () => {
+      // 前回の保存で更新されたバージョンを使う
+      const memo = currentMemoRef.current ?? scheduledMemo;
       try {
         const title = memo.title || '無題';
-        const newFilename = await saveMemo(title, editingContent, memo.filename);
+        const saved = await saveMemoMerging(title, editingContent, memo.filename, memo.version);
 
-        // ファイル名が変更された場合は親に通知
-        if (newFilename !== memo.filename && onSavedRef.current) {
-          onSavedRef.current(newFilename);
-        }
+        // 新しいファイル名とバージョンを親に通知
+        onSavedRef.current?.(saved, memo.filename, editingContent);
 
-        previousContentRef.current = editingContent;
-        console.log('自動保存完了:', newFilename);
+        previousContentRef.current = saved.content;
+        console.log('自動保存完了:', saved.filename);
       } catch (error) {
-        console.error('自動保存に失敗しました:', error);
+        if (isConflict(error) && onConflictRef.current) {
+          onConflictRef.current(memo, editingContent, error);
+        } else {
+          console.error('自動保存に失敗しました:', error);
+        }
       }
     }, delay);
 
@@ -1,6 +1,6 @@
 import { invoke } from '@tauri-apps/api/tauri';
 import { Memo } from '../types/memo';
-import { MemoMetadata } from './tauriTypes';
+import { CommandError, MemoMerge, MemoMetadata, PinToggle, SavedMemo } from './tauriTypes';
 
 /**
  * すべてのメモを読み込む
@@ -39,27 +39,71 @@ export async function loadMemo(filename: string): Promise<Memo | null> {
 }
 
 /**
- * メモを保存する
+ * メモを保存し、新しいファイル名とバージョンを返す
  * タイトルが変更された場合は古いファイルを削除して新しいファイルを作成する
+ * expectedVersion を渡すと、読み込み後に他のプログラムが変更していた場合は
+ * 上書きせずに kind: 'conflict' のエラーになる
  */
 export async function saveMemo(
   title: string,
   content: string,
-  oldFilename?: string
-): Promise<string> {
+  oldFilename?: string,
+  expectedVersion?: string
+): Promise<SavedMemo> {
   try {
-    const newFilename = await invoke<string>('save_memo', {
+    return await invoke<SavedMemo>('save_memo', {
       title,
       content,
-      oldFilename: oldFilename || null
+      oldFilename: oldFilename || null,
+      expectedVersion: expectedVersion || null
     });
-    return newFilename;
   } catch (error) {
     console.error('メモの保存に失敗しました:', error);
     throw error;
   }
 }
 
+/**
+ * 保存が競合エラーになったかどうか
+ */
+export function isConflict(error: unknown): error is CommandError {
+  return typeof error === 'object' && error !== null && (error as CommandError).kind === 'conflict';
+}
+
+/**
+ * baseVersion から編集した内容を、その後に他のプログラムが書いた内容とマージする
+ */
+export async function mergeMemo(filename: string, baseVersion: string, content: string): Promise<MemoMerge> {
+  return invoke<MemoMerge>('merge_memo', { filename, baseVersion, content });
+}
+
+/**
+ * expectedVersion を付けて保存し、他のプログラムの変更と競合した場合は
+ * マージして保存し直す。保存した内容も返す
+ * きれいにマージできない場合は競合エラーをそのまま投げる
+ */
+export async function saveMemoMerging(
+  title: string,
+  content: string,
+  filename: string,
+  expectedVersion?: string
+): Promise<SavedMemo & { content: string }> {
+  try {
+    const saved = await saveMemo(title, content, filename, expectedVersion);
+    return { ...saved, content };
+  } catch (error) {
+    if (!isConflict(error) || !expectedVersion) {
+      throw error;
+    }
+    const merge = await mergeMemo(filename, expectedVersion, content).catch(() => null);
+    if (!merge?.clean) {
+      throw error;
+    }
+    const saved = await saveMemo(title, merge.content, filename, merge.version);
+    return { ...saved, content: merge.content };
+  }
+}
+
 /**
  * メモを削除する
  */
@@ -92,11 +136,11 @@ export async function createMemo(extension: string = 'md'): Promise<Memo> {
 
 /**
  * メモのピン状態を切り替える
+ * フロントマターにも書き込まれるため、新しいバージョンも返す
  */
-export async function togglePin(filename: string): Promise<boolean> {
+export async function togglePin(filename: string): Promise<PinToggle> {
   try {
-    const isPinned = await invoke<boolean>('toggle_pin', { filename });
-    return isPinned;
+    return await invoke<PinToggle>('toggle_pin', { filename });
   } catch (error) {
     console.error(`メモ "${filename}" のピン切り替えに失敗しました:`, error);
     throw error;
@@ -1,6 +1,7 @@
 // Types matching Rust command responses
 
 export interface MemoMetadata {
+  id: string;  // stays the same when the memo is renamed; empty until listed for memos added outside the app
   filename: string;
   title: string;
   content: string;
@@ -8,9 +9,39 @@ export interface MemoMetadata {
   updatedAt: string;  // ISO 8601 string from Rust
   pinned: boolean;
   pinnedAt: string | null;  // ISO 8601 string from Rust or null
+  encrypted: boolean;
+  locked: boolean;  // encrypted while the vault is locked: title and content are empty
+  version: string;  // hash of the file on disk, passed back to save_memo to detect outside changes
+}
+
+export interface SavedMemo {
+  filename: string;  // follows the title, so it changes when the title does
+  version: string;   // hash of the file as written, passed to the next save_memo
+}
+
+export interface PinToggle {
+  pinned: boolean;
+  version: string;  // version of the memo afterwards; the pin is also written to its front matter
+}
+
+export interface MemoMerge {
+  content: string;
+  clean: boolean;  // false: content has conflict markers where both sides changed the same lines
+  conflicts: { line: number; base: string; ours: string; theirs: string }[];
+  version: string;  // version of the memo on disk the merge was made against
 }
 
 export interface AppConfigRust {
   memoDirectory: string;
   autoSaveDelay: number;
 }
+
+// Error rejected by the memo store commands
+export interface CommandError {
+  kind: 'notFound' | 'alreadyExists' | 'invalidInput' | 'io' | 'format' | 'locked' | 'conflict' | 'other';
+  message: string;
+  path?: string | null;
+  ioKind?: 'notFound' | 'permissionDenied' | 'alreadyExists' | 'storageFull' | 'invalidData' | 'other';
+  currentContent?: string | null;  // conflict: what is on disk now, null if the memo was deleted
+  currentVersion?: string | null;
+}
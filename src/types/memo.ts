@@ -1,4 +1,5 @@
 export interface Memo {
+  id?: string;         // ファイル名が変わっても変わらないID
   filename: string;    // ファイル名（例: "買い物リスト.md"）
   title: string;       // 表示用タイトル（例: "買い物リスト"）
   content: string;     // メモ本文
@@ -6,4 +7,5 @@ export interface Memo {
   updatedAt: Date;     // 更新日時
   pinned: boolean;     // ピン留めされているか
   pinnedAt: Date | null; // ピン留めされた日時（ピン留めされていない場合はnull）
+  version?: string;    // 読み込んだ時点のファイルのハッシュ（保存時の競合検出用）
 }